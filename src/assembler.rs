@@ -0,0 +1,322 @@
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+
+use crate::console::{Instruction, OpCode, Operands, Register};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AssembleErrorKind {
+    UnknownOpCode,
+    MissingOperand,
+    UnexpectedOperand,
+    InvalidNumber,
+    UnknownRegister,
+    InvalidLabel,
+    /// The label was already defined on `first_line`.
+    DuplicateLabel {
+        first_line: usize,
+    },
+    UnknownLabel,
+    /// The label is too far away to reach with a jump offset.
+    LabelOutOfRange,
+}
+
+/// Describes where a program failed to assemble. `line` and `column` are 1-based, and `found`
+/// is the word at that position, or empty at the end of the line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AssembleError {
+    pub line: usize,
+    pub column: usize,
+    pub found: String,
+    pub kind: AssembleErrorKind,
+}
+
+impl fmt::Display for AssembleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let expected = match &self.kind {
+            AssembleErrorKind::UnknownOpCode => "expected an instruction".to_string(),
+            AssembleErrorKind::MissingOperand => "expected an operand".to_string(),
+            AssembleErrorKind::UnexpectedOperand => "expected the end of the line".to_string(),
+            AssembleErrorKind::InvalidNumber => "expected a signed number".to_string(),
+            AssembleErrorKind::UnknownRegister => "expected a register".to_string(),
+            AssembleErrorKind::InvalidLabel => "expected a label name".to_string(),
+            AssembleErrorKind::DuplicateLabel { first_line } => {
+                format!("label already defined on line {}", first_line)
+            }
+            AssembleErrorKind::UnknownLabel => "expected a defined label".to_string(),
+            AssembleErrorKind::LabelOutOfRange => "expected a label within range".to_string(),
+        };
+        write!(
+            f,
+            "line {}, column {}: {}, found {:?}",
+            self.line, self.column, expected, self.found
+        )
+    }
+}
+
+impl Error for AssembleError {}
+
+/// A word of a source line, with the 1-based column it starts at.
+#[derive(Debug, Clone, Copy)]
+struct Word<'a> {
+    column: usize,
+    text: &'a str,
+}
+
+impl<'a> Word<'a> {
+    fn error(&self, line: usize, kind: AssembleErrorKind) -> AssembleError {
+        AssembleError {
+            line,
+            column: self.column,
+            found: self.text.to_string(),
+            kind,
+        }
+    }
+}
+
+/// Splits a line into words at whitespace, dropping any `#` comment.
+fn words(line: &str) -> Vec<Word<'_>> {
+    let code = line.split('#').next().unwrap();
+    let mut words = vec![];
+    let mut start = None;
+    for (column, (offset, c)) in code
+        .char_indices()
+        .chain(Some((code.len(), ' ')))
+        .enumerate()
+    {
+        match (start, c.is_whitespace()) {
+            (None, false) => start = Some((column + 1, offset)),
+            (Some((column, begin)), true) => {
+                words.push(Word {
+                    column,
+                    text: &code[begin..offset],
+                });
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    words
+}
+
+fn is_label(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn parse_number(word: &Word<'_>, line: usize) -> Result<i32, AssembleError> {
+    let digits = word.text.trim_start_matches(['+', '-']);
+    if digits.len() + 1 < word.text.len() || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return Err(word.error(line, AssembleErrorKind::InvalidNumber));
+    }
+    word.text
+        .parse()
+        .map_err(|_| word.error(line, AssembleErrorKind::InvalidNumber))
+}
+
+/// A jump whose offset is a label, to fill in once every label is known.
+struct LabelUse<'a> {
+    pc: usize,
+    line: usize,
+    word: Word<'a>,
+}
+
+/// Reads a program written one instruction per line, such as `jmp +4` or `add r1 -2`.
+///
+/// Anything after a `#` is a comment, and blank lines are ignored. A line can start with one or
+/// more labels, written `name:`, which mark the address of the next instruction; jump offsets can
+/// then name a label instead of giving a number.
+pub fn assemble(source: &str) -> Result<Vec<Instruction>, AssembleError> {
+    let mut program = vec![];
+    let mut labels: HashMap<&str, (usize, usize)> = HashMap::new();
+    let mut label_uses = vec![];
+
+    for (idx, text) in source.lines().enumerate() {
+        let line = idx + 1;
+        let mut words = &words(text)[..];
+
+        while let Some((word, rest)) = words.split_first() {
+            let name = match word.text.strip_suffix(':') {
+                Some(name) => name,
+                None => break,
+            };
+            if !is_label(name) {
+                return Err(word.error(line, AssembleErrorKind::InvalidLabel));
+            }
+            if let Some((_, first_line)) = labels.insert(name, (program.len(), line)) {
+                return Err(word.error(line, AssembleErrorKind::DuplicateLabel { first_line }));
+            }
+            words = rest;
+        }
+
+        let (name, operands) = match words.split_first() {
+            Some(split) => split,
+            None => continue,
+        };
+        let op_code = OpCode::from_name(name.text)
+            .ok_or_else(|| name.error(line, AssembleErrorKind::UnknownOpCode))?;
+        let kind = op_code.info().operands;
+        let expected = match kind {
+            Operands::None => 0,
+            Operands::Value | Operands::Offset => 1,
+            Operands::RegisterValue | Operands::RegisterOffset => 2,
+        };
+        if let Some(extra) = operands.get(expected) {
+            return Err(extra.error(line, AssembleErrorKind::UnexpectedOperand));
+        }
+        if operands.len() < expected {
+            let end = text.split('#').next().unwrap().trim_end();
+            return Err(AssembleError {
+                line,
+                column: end.chars().count() + 1,
+                found: String::new(),
+                kind: AssembleErrorKind::MissingOperand,
+            });
+        }
+
+        let mut instruction = Instruction::new(op_code, 0);
+        let mut operands = operands.iter();
+        if let Operands::RegisterValue | Operands::RegisterOffset = kind {
+            let word = operands.next().unwrap();
+            instruction.register = Register::from_name(word.text)
+                .ok_or_else(|| word.error(line, AssembleErrorKind::UnknownRegister))?;
+        }
+        if let Some(word) = operands.next() {
+            let is_offset = matches!(kind, Operands::Offset | Operands::RegisterOffset);
+            if is_offset && is_label(word.text) {
+                label_uses.push(LabelUse {
+                    pc: program.len(),
+                    line,
+                    word: *word,
+                });
+            } else {
+                instruction.operand = parse_number(word, line)?;
+            }
+        }
+        program.push(instruction);
+    }
+
+    for label_use in label_uses {
+        let error = |kind| label_use.word.error(label_use.line, kind);
+        let (target, _) = labels
+            .get(label_use.word.text)
+            .ok_or_else(|| error(AssembleErrorKind::UnknownLabel))?;
+        let offset = i64::try_from(*target)
+            .ok()
+            .zip(i64::try_from(label_use.pc).ok());
+        program[label_use.pc].operand = offset
+            .and_then(|(target, pc)| i32::try_from(target - pc).ok())
+            .ok_or_else(|| error(AssembleErrorKind::LabelOutOfRange))?;
+    }
+
+    Ok(program)
+}
+
+/// Writes a program in canonical form, one instruction per line, which assembles back to the
+/// same program.
+pub fn disassemble(program: &[Instruction]) -> String {
+    program
+        .iter()
+        .map(|instruction| format!("{}\n", instruction))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn labels_and_comments() {
+        let source = "# counts r1 down from 3
+    add r1 +3
+loop: jz r1 done   # leave once r1 is empty
+    acc +2
+
+    add r1 -1
+    jmp loop
+done: end:
+    hlt";
+        let program = assemble(source).unwrap();
+        assert_eq!(
+            disassemble(&program),
+            "add r1 +3\njz r1 +4\nacc +2\nadd r1 -1\njmp -3\nhlt\n"
+        );
+        assert_eq!(assemble(&disassemble(&program)), Ok(program));
+    }
+
+    #[test]
+    fn round_trip() {
+        let source = "nop +0\nacc +1\njmp +4\nacc +3\njmp -3\nacc -99\nacc +1\njmp -4\nacc +6\n";
+        assert_eq!(disassemble(&assemble(source).unwrap()), source);
+        assert_eq!(
+            assemble("acc 12\nnop label\nlabel: jnz r3 -0").unwrap(),
+            vec![
+                Instruction::new(OpCode::Acc, 12),
+                Instruction::new(OpCode::Nop, 1),
+                Instruction::with_register(OpCode::Jnz, Register::from_name("r3").unwrap(), 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let error = |source: &str| {
+            let e = assemble(source).unwrap_err();
+            (e.line, e.column, e.kind, e.found)
+        };
+        assert_eq!(
+            error("nop +0\n  mul +2"),
+            (2, 3, AssembleErrorKind::UnknownOpCode, "mul".to_string())
+        );
+        assert_eq!(
+            error("acc"),
+            (1, 4, AssembleErrorKind::MissingOperand, "".to_string())
+        );
+        assert_eq!(
+            error("add r1  # comment"),
+            (1, 7, AssembleErrorKind::MissingOperand, "".to_string())
+        );
+        assert_eq!(
+            error("hlt +1"),
+            (1, 5, AssembleErrorKind::UnexpectedOperand, "+1".to_string())
+        );
+        assert_eq!(
+            error("acc +-1"),
+            (1, 5, AssembleErrorKind::InvalidNumber, "+-1".to_string())
+        );
+        assert_eq!(
+            error("acc start\nstart: nop +0"),
+            (1, 5, AssembleErrorKind::InvalidNumber, "start".to_string())
+        );
+        assert_eq!(
+            error("add acc +1\nadd r9 +1"),
+            (2, 5, AssembleErrorKind::UnknownRegister, "r9".to_string())
+        );
+        assert_eq!(
+            error("9lives: nop +0"),
+            (1, 1, AssembleErrorKind::InvalidLabel, "9lives:".to_string())
+        );
+        assert_eq!(
+            error("a: nop +0\n a: hlt"),
+            (
+                2,
+                2,
+                AssembleErrorKind::DuplicateLabel { first_line: 1 },
+                "a:".to_string()
+            )
+        );
+        assert_eq!(
+            error("jmp nowhere"),
+            (1, 5, AssembleErrorKind::UnknownLabel, "nowhere".to_string())
+        );
+
+        assert_eq!(
+            assemble("nop +0\njmp +x").unwrap_err().to_string(),
+            "line 2, column 5: expected a signed number, found \"+x\""
+        );
+    }
+}
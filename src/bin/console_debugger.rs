@@ -0,0 +1,348 @@
+//! An interactive single-step debugger for handheld console programs.
+//!
+//! Usage: `console_debugger <program file>`, then type `help` for the commands.
+
+use std::env;
+use std::fmt;
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::process;
+
+use advent_of_code_2020::assembler::assemble;
+use advent_of_code_2020::console::{Instruction, Register, State, TraceStep, Vm};
+
+const HELP: &str = "commands:
+  s, step [n]          run the next n instructions (default 1)
+  c, continue          run until a breakpoint or until the program stops
+  b, break pc <n>      stop before running the instruction at pc n
+  b, break acc <n>     stop when an instruction sets the accumulator to n
+  d, delete <n>        remove breakpoint n
+  i, info              list the breakpoints
+  p, print             show the machine state and next instruction
+  t, trace [json]      run until the program stops, printing every step as text or JSON
+  r, restart           start the program again, keeping the breakpoints
+  h, help              show this message
+  q, quit              leave the debugger";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Breakpoint {
+    Pc(usize),
+    Accumulator(i64),
+}
+
+impl Breakpoint {
+    /// Whether running `step`, which left the machine in `state`, should stop the machine. An
+    /// accumulator breakpoint only fires when the step changes the accumulator to its value.
+    fn is_hit(&self, step: &TraceStep, state: &State) -> bool {
+        let acc = Register::ACC.index();
+        match self {
+            Breakpoint::Pc(pc) => state.pc == *pc,
+            Breakpoint::Accumulator(value) => {
+                step.registers_before[acc] != *value && step.registers_after[acc] == *value
+            }
+        }
+    }
+}
+
+impl fmt::Display for Breakpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Breakpoint::Pc(pc) => write!(f, "pc {}", pc),
+            Breakpoint::Accumulator(value) => write!(f, "acc {}", value),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Command {
+    Step(usize),
+    Continue,
+    Break(Breakpoint),
+    Delete(usize),
+    Info,
+    Print,
+    Trace { json: bool },
+    Restart,
+    Help,
+    Quit,
+}
+
+fn parse_number<T: std::str::FromStr>(word: Option<&str>) -> Result<T, String> {
+    let word = word.ok_or_else(|| "expected a number".to_string())?;
+    word.parse()
+        .map_err(|_| format!("expected a number, found {:?}", word))
+}
+
+fn parse_command(line: &str) -> Result<Command, String> {
+    let mut words = line.split_whitespace();
+    let command = match words.next() {
+        Some("s") | Some("step") => match words.next() {
+            None => Command::Step(1),
+            count => Command::Step(parse_number(count)?),
+        },
+        Some("c") | Some("continue") => Command::Continue,
+        Some("b") | Some("break") => match words.next() {
+            Some("pc") => Command::Break(Breakpoint::Pc(parse_number(words.next())?)),
+            Some("acc") => Command::Break(Breakpoint::Accumulator(parse_number(words.next())?)),
+            _ => return Err("expected `break pc <n>` or `break acc <n>`".to_string()),
+        },
+        Some("d") | Some("delete") => Command::Delete(parse_number(words.next())?),
+        Some("i") | Some("info") => Command::Info,
+        Some("p") | Some("print") => Command::Print,
+        Some("t") | Some("trace") => match words.next() {
+            None => Command::Trace { json: false },
+            Some("json") => Command::Trace { json: true },
+            Some(other) => {
+                return Err(format!(
+                    "expected `trace` or `trace json`, found {:?}",
+                    other
+                ))
+            }
+        },
+        Some("r") | Some("restart") => Command::Restart,
+        Some("h") | Some("help") => Command::Help,
+        Some("q") | Some("quit") => Command::Quit,
+        Some(other) => return Err(format!("unknown command {:?}, try `help`", other)),
+        None => return Err("expected a command, try `help`".to_string()),
+    };
+    match words.next() {
+        Some(extra) => Err(format!("unexpected {:?}", extra)),
+        None => Ok(command),
+    }
+}
+
+struct Debugger {
+    program: Vec<Instruction>,
+    vm: Vm,
+    breakpoints: Vec<Breakpoint>,
+}
+
+impl Debugger {
+    fn new(program: Vec<Instruction>) -> Self {
+        Debugger {
+            vm: Vm::new(program.clone()),
+            program,
+            breakpoints: vec![],
+        }
+    }
+
+    fn print_state<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        let state = self.vm.state();
+        write!(out, "pc {}, acc {}", state.pc, state.accumulator())?;
+        match (self.vm.next_instruction(), self.vm.termination()) {
+            (Some(instruction), _) => writeln!(out, ", next: {}", instruction),
+            (None, Some(termination)) => writeln!(out, ", {}", termination),
+            (None, None) => writeln!(out),
+        }
+    }
+
+    /// Carries out a command, returning false once the user has asked to quit.
+    fn execute<W: Write>(&mut self, command: Command, out: &mut W) -> io::Result<bool> {
+        match command {
+            Command::Step(count) => {
+                for _ in 0..count {
+                    match self.vm.traced_step() {
+                        Some(step) => writeln!(out, "{}", step)?,
+                        None => break,
+                    }
+                }
+                if let Some(termination) = self.vm.termination() {
+                    writeln!(out, "{}", termination)?;
+                }
+            }
+            Command::Continue => {
+                // breakpoints are checked after each step, so continuing from one always moves
+                let mut hit = vec![];
+                while hit.is_empty() {
+                    let step = match self.vm.traced_step() {
+                        Some(step) => step,
+                        None => break,
+                    };
+                    let state = self.vm.state();
+                    hit = (1..)
+                        .zip(&self.breakpoints)
+                        .filter(|(_, breakpoint)| breakpoint.is_hit(&step, &state))
+                        .collect();
+                }
+                match self.vm.termination() {
+                    Some(termination) => writeln!(out, "{}", termination)?,
+                    None => {
+                        for (number, breakpoint) in hit {
+                            writeln!(out, "breakpoint {}: {}", number, breakpoint)?;
+                        }
+                    }
+                }
+                self.print_state(out)?;
+            }
+            Command::Break(breakpoint) => {
+                self.breakpoints.push(breakpoint);
+                writeln!(out, "breakpoint {}: {}", self.breakpoints.len(), breakpoint)?;
+            }
+            Command::Delete(number) => {
+                if number == 0 || number > self.breakpoints.len() {
+                    writeln!(out, "no breakpoint {}", number)?;
+                } else {
+                    let breakpoint = self.breakpoints.remove(number - 1);
+                    writeln!(out, "deleted breakpoint {}: {}", number, breakpoint)?;
+                }
+            }
+            Command::Info => {
+                if self.breakpoints.is_empty() {
+                    writeln!(out, "no breakpoints")?;
+                }
+                for (idx, breakpoint) in self.breakpoints.iter().enumerate() {
+                    writeln!(out, "breakpoint {}: {}", idx + 1, breakpoint)?;
+                }
+            }
+            Command::Print => self.print_state(out)?,
+            Command::Trace { json } => {
+                let trace = self.vm.trace();
+                if json {
+                    writeln!(out, "{}", trace.to_json())?;
+                } else {
+                    write!(out, "{}", trace.to_text())?;
+                }
+            }
+            Command::Restart => {
+                self.vm = Vm::new(self.program.clone());
+                self.print_state(out)?;
+            }
+            Command::Help => writeln!(out, "{}", HELP)?,
+            Command::Quit => return Ok(false),
+        }
+        Ok(true)
+    }
+}
+
+fn main() -> io::Result<()> {
+    let path = match env::args().nth(1) {
+        Some(path) => path,
+        None => {
+            eprintln!("usage: console_debugger <program file>");
+            process::exit(2);
+        }
+    };
+    let source = fs::read_to_string(&path)?;
+    let program = match assemble(&source) {
+        Ok(program) => program,
+        Err(e) => {
+            eprintln!("{}: {}", path, e);
+            process::exit(1);
+        }
+    };
+    let mut debugger = Debugger::new(program);
+    let stdin = io::stdin();
+    let mut stdout = io::stdout();
+
+    debugger.print_state(&mut stdout)?;
+    for line in stdin.lock().lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        match parse_command(&line) {
+            Ok(command) => {
+                if !debugger.execute(command, &mut stdout)? {
+                    break;
+                }
+            }
+            Err(message) => writeln!(stdout, "{}", message)?,
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session(program: &str, commands: &[&str]) -> String {
+        let mut debugger = Debugger::new(assemble(program).unwrap());
+        let mut out = vec![];
+        for command in commands {
+            match parse_command(command) {
+                Ok(command) => {
+                    debugger.execute(command, &mut out).unwrap();
+                }
+                Err(message) => writeln!(out, "{}", message).unwrap(),
+            }
+        }
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn breakpoints() {
+        let program = "nop +0\nacc +1\njmp +4\nacc +3\njmp -3\nacc -99\nacc +1\njmp -4\nacc +6";
+        assert_eq!(
+            session(
+                program,
+                &["b acc 1", "break pc 4", "c", "c", "s", "d 2", "c", "jump"]
+            ),
+            "breakpoint 1: acc 1
+breakpoint 2: pc 4
+breakpoint 1: acc 1
+pc 2, acc 1, next: jmp +4
+breakpoint 2: pc 4
+pc 4, acc 5, next: jmp -3
+    4  jmp -3       acc 5 -> 5
+infinite loop at pc 1
+deleted breakpoint 2: pc 4
+infinite loop at pc 1
+pc 1, acc 5, infinite loop at pc 1
+unknown command \"jump\", try `help`
+"
+        );
+    }
+
+    #[test]
+    fn accumulator_breakpoints_fire_on_change() {
+        let program = "acc +1\nnop +0\nacc +0\nacc -1\nacc +1\nhlt";
+        assert_eq!(
+            session(program, &["b acc 1", "c", "c", "c"]),
+            "breakpoint 1: acc 1
+breakpoint 1: acc 1
+pc 1, acc 1, next: nop +0
+breakpoint 1: acc 1
+pc 5, acc 1, next: hlt
+halted
+pc 5, acc 1, halted
+"
+        );
+    }
+
+    #[test]
+    fn tracing() {
+        let program = "add r1 +2\nacc +1\njmp -1";
+        assert_eq!(
+            session(program, &["s", "trace", "r", "t json"]),
+            r#"    0  add r1 +2    r1 0 -> 2
+    1  acc +1       acc 0 -> 1
+    2  jmp -1       acc 1 -> 1
+infinite loop at pc 1
+pc 0, acc 0, next: add r1 +2
+{"steps":[{"pc":0,"op_code":"add","register":"r1","operand":2,"registers_before":[0,0,0,0],"registers_after":[0,2,0,0]},
+{"pc":1,"op_code":"acc","operand":1,"registers_before":[0,2,0,0],"registers_after":[1,2,0,0]},
+{"pc":2,"op_code":"jmp","operand":-1,"registers_before":[1,2,0,0],"registers_after":[1,2,0,0]}],
+"termination":"infinite loop at pc 1"}
+"#
+        );
+    }
+
+    #[test]
+    fn commands() {
+        assert_eq!(parse_command("step"), Ok(Command::Step(1)));
+        assert_eq!(parse_command(" s  10 "), Ok(Command::Step(10)));
+        assert_eq!(
+            parse_command("b acc -5"),
+            Ok(Command::Break(Breakpoint::Accumulator(-5)))
+        );
+        assert!(parse_command("b pc -5").is_err());
+        assert!(parse_command("q now").is_err());
+        assert_eq!(parse_command("t"), Ok(Command::Trace { json: false }));
+        assert_eq!(
+            parse_command("trace json"),
+            Ok(Command::Trace { json: true })
+        );
+        assert!(parse_command("trace xml").is_err());
+    }
+}
@@ -0,0 +1,108 @@
+//! Writes a report of the password records that break a set of policies, one row or object per
+//! failing record.
+//!
+//! Usage: `password_report <csv|json> <password file> [policy,...]`. The policies default to the
+//! two from the puzzle.
+
+use std::env;
+use std::error::Error;
+use std::fs;
+use std::io::{self, Write};
+use std::process;
+
+use advent_of_code_2020::day2::{
+    failing_reports, get_values, policies_from_names, report_csv, report_json,
+};
+
+const USAGE: &str = "usage: password_report <csv|json> <password file> [policy,...]";
+
+const DEFAULT_POLICIES: &str = "count-in-range,exactly-one-position";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Csv,
+    Json,
+}
+
+fn parse_format(name: &str) -> Option<Format> {
+    match name {
+        "csv" => Some(Format::Csv),
+        "json" => Some(Format::Json),
+        _ => None,
+    }
+}
+
+fn report(format: Format, input: &str, policies: &str) -> Result<String, Box<dyn Error>> {
+    let records = get_values(input)?;
+    let policies = policies_from_names(policies)?;
+    let reports = failing_reports(&records, &policies);
+    Ok(match format {
+        Format::Csv => report_csv(&reports),
+        Format::Json => report_json(&reports) + "\n",
+    })
+}
+
+fn main() -> io::Result<()> {
+    let args = env::args().skip(1).collect::<Vec<String>>();
+    let (format, path, policies) = match &args[..] {
+        [format, path] => (format, path, DEFAULT_POLICIES),
+        [format, path, policies] => (format, path, policies.as_str()),
+        _ => {
+            eprintln!("{}", USAGE);
+            process::exit(2);
+        }
+    };
+    let format = match parse_format(format) {
+        Some(format) => format,
+        None => {
+            eprintln!("{}", USAGE);
+            process::exit(2);
+        }
+    };
+    let output = fs::read_to_string(path)
+        .map_err(|e| e.into())
+        .and_then(|input| report(format, &input, policies));
+    match output {
+        Ok(output) => io::stdout().write_all(output.as_bytes()),
+        Err(e) => {
+            eprintln!("{}: {}", path, e);
+            process::exit(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RECORDS: &str = "1-3 a: abcde\n1-3 b: cdefg\n2-9 c: ccccccccc";
+
+    #[test]
+    fn reports_failing_records() {
+        assert_eq!(
+            report(Format::Csv, RECORDS, DEFAULT_POLICIES).unwrap(),
+            "line,minimum_count,maximum_count,letter,password,policy,reason\n\
+             2,1,3,b,cdefg,count-in-range,\"letter 'b' appears 0 times, min 1\"\n\
+             2,1,3,b,cdefg,exactly-one-position,letter 'b' is at neither position 1 nor 3\n\
+             3,2,9,c,ccccccccc,exactly-one-position,letter 'c' is at both positions 2 and 9\n"
+        );
+        assert_eq!(
+            report(Format::Json, RECORDS, "count-in-range").unwrap(),
+            "[{\"line\":2,\"minimum_count\":1,\"maximum_count\":3,\"letter\":\"b\",\"password\":\"cdefg\",\"valid\":false,\"violations\":[\
+             {\"policy\":\"count-in-range\",\"reason\":\"letter 'b' appears 0 times, min 1\"}]}]\n"
+        );
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(parse_format("json"), Some(Format::Json));
+        assert_eq!(parse_format("xml"), None);
+        assert_eq!(
+            report(Format::Csv, RECORDS, "count-in-range,shortest")
+                .unwrap_err()
+                .to_string(),
+            "unknown password policy \"shortest\""
+        );
+        assert!(report(Format::Csv, "1-3 a abcde", DEFAULT_POLICIES).is_err());
+    }
+}
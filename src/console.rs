@@ -0,0 +1,797 @@
+use std::collections::HashSet;
+use std::convert::TryFrom;
+use std::fmt;
+
+/// The number of registers, including the accumulator.
+pub const REGISTER_COUNT: usize = 4;
+
+const REGISTER_NAMES: [&str; REGISTER_COUNT] = ["acc", "r1", "r2", "r3"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Register(usize);
+
+impl Register {
+    pub const ACC: Register = Register(0);
+
+    pub fn from_name(name: &str) -> Option<Register> {
+        REGISTER_NAMES.iter().position(|n| *n == name).map(Register)
+    }
+
+    pub fn name(&self) -> &'static str {
+        REGISTER_NAMES[self.0]
+    }
+
+    pub fn index(&self) -> usize {
+        self.0
+    }
+}
+
+impl fmt::Display for Register {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// The operations understood by the handheld game console. Each one also needs an entry in
+/// `OP_CODES` and a case in `Instruction::register_change` or `Instruction::next_pc`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OpCode {
+    Nop,
+    Acc,
+    Jmp,
+    Add,
+    Jz,
+    Jnz,
+    Hlt,
+}
+
+/// What an instruction takes after its name, and so how it's written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operands {
+    None,
+    /// A signed number, such as `+5`.
+    Value,
+    /// A signed jump offset, which can also be written as a label.
+    Offset,
+    /// A register and then a signed number.
+    RegisterValue,
+    /// A register and then a signed jump offset or label.
+    RegisterOffset,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OpCodeInfo {
+    pub op_code: OpCode,
+    pub name: &'static str,
+    pub operands: Operands,
+    pub description: &'static str,
+}
+
+pub const OP_CODES: [OpCodeInfo; 7] = [
+    OpCodeInfo {
+        op_code: OpCode::Nop,
+        name: "nop",
+        operands: Operands::Offset,
+        description: "do nothing; the offset is ignored",
+    },
+    OpCodeInfo {
+        op_code: OpCode::Acc,
+        name: "acc",
+        operands: Operands::Value,
+        description: "add the value to the accumulator",
+    },
+    OpCodeInfo {
+        op_code: OpCode::Jmp,
+        name: "jmp",
+        operands: Operands::Offset,
+        description: "jump by the offset",
+    },
+    OpCodeInfo {
+        op_code: OpCode::Add,
+        name: "add",
+        operands: Operands::RegisterValue,
+        description: "add the value to the register",
+    },
+    OpCodeInfo {
+        op_code: OpCode::Jz,
+        name: "jz",
+        operands: Operands::RegisterOffset,
+        description: "jump by the offset if the register is zero",
+    },
+    OpCodeInfo {
+        op_code: OpCode::Jnz,
+        name: "jnz",
+        operands: Operands::RegisterOffset,
+        description: "jump by the offset unless the register is zero",
+    },
+    OpCodeInfo {
+        op_code: OpCode::Hlt,
+        name: "hlt",
+        operands: Operands::None,
+        description: "stop the program",
+    },
+];
+
+impl OpCode {
+    pub fn info(&self) -> &'static OpCodeInfo {
+        OP_CODES.iter().find(|info| info.op_code == *self).unwrap()
+    }
+
+    pub fn from_name(name: &str) -> Option<OpCode> {
+        OP_CODES
+            .iter()
+            .find(|info| info.name == name)
+            .map(|info| info.op_code)
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.info().name
+    }
+
+    /// Whether the instruction's effect on the program counter depends on the registers.
+    pub fn is_conditional(&self) -> bool {
+        matches!(self, OpCode::Jz | OpCode::Jnz)
+    }
+}
+
+impl fmt::Display for OpCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// An instruction and its operands. `register` is `Register::ACC` for operations that don't take
+/// one, and `operand` is 0 for operations that don't take a number.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Instruction {
+    pub op_code: OpCode,
+    pub register: Register,
+    pub operand: i32,
+}
+
+/// Writes the instruction in its canonical form, which the assembler reads back to the same
+/// instruction.
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.op_code.info().operands {
+            Operands::None => write!(f, "{}", self.op_code),
+            Operands::Value | Operands::Offset => write!(f, "{} {:+}", self.op_code, self.operand),
+            Operands::RegisterValue | Operands::RegisterOffset => {
+                write!(f, "{} {} {:+}", self.op_code, self.register, self.operand)
+            }
+        }
+    }
+}
+
+impl Instruction {
+    pub fn new(op_code: OpCode, operand: i32) -> Self {
+        Instruction {
+            op_code,
+            register: Register::ACC,
+            operand,
+        }
+    }
+
+    pub fn with_register(op_code: OpCode, register: Register, operand: i32) -> Self {
+        Instruction {
+            op_code,
+            register,
+            operand,
+        }
+    }
+
+    /// The register this instruction adds to, and by how much.
+    pub fn register_change(&self) -> Option<(Register, i64)> {
+        match self.op_code {
+            OpCode::Acc => Some((Register::ACC, i64::from(self.operand))),
+            OpCode::Add => Some((self.register, i64::from(self.operand))),
+            OpCode::Nop | OpCode::Jmp | OpCode::Jz | OpCode::Jnz | OpCode::Hlt => None,
+        }
+    }
+
+    /// How running this instruction changes the accumulator.
+    pub fn accumulator_change(&self) -> i64 {
+        match self.register_change() {
+            Some((Register::ACC, change)) => change,
+            _ => 0,
+        }
+    }
+
+    /// The address that runs after this instruction when the machine is in `state` and the
+    /// program has `len` instructions, or why the machine stops instead.
+    pub fn next_pc(&self, state: &State, len: usize) -> Result<usize, Termination> {
+        let pc = state.pc;
+        let register_is_zero = state.registers[self.register.index()] == 0;
+        let jumps = match self.op_code {
+            OpCode::Hlt => return Err(Termination::Halted),
+            OpCode::Acc | OpCode::Add | OpCode::Nop => false,
+            OpCode::Jmp => true,
+            OpCode::Jz => register_is_zero,
+            OpCode::Jnz => !register_is_zero,
+        };
+        if !jumps {
+            return Ok(pc + 1);
+        }
+
+        // an address that doesn't fit in an i64 is certainly past the end
+        let target = jump_target(pc, self.operand).unwrap_or(i64::MAX);
+        match usize::try_from(target) {
+            Err(_) => Err(Termination::JumpedBeforeStart { pc, target }),
+            Ok(next_pc) if next_pc > len => Err(Termination::JumpedPastEnd { pc, target }),
+            Ok(next_pc) => Ok(next_pc),
+        }
+    }
+}
+
+/// A snapshot of the machine registers between instructions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct State {
+    /// The 0-based address of the next instruction to run.
+    pub pc: usize,
+    /// Indexed by `Register::index`, so the accumulator comes first.
+    pub registers: [i64; REGISTER_COUNT],
+}
+
+impl State {
+    pub fn accumulator(&self) -> i64 {
+        self.registers[Register::ACC.index()]
+    }
+}
+
+/// Why a machine stopped running.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Termination {
+    /// The program counter reached the address just past the last instruction, or the machine
+    /// ran `hlt`.
+    Halted,
+    /// The machine was about to run the instruction at `pc` in a state it had already been in.
+    InfiniteLoop { pc: usize },
+    /// The jump at `pc` landed on `target`, a negative address.
+    JumpedBeforeStart { pc: usize, target: i64 },
+    /// The jump at `pc` landed on `target`, beyond the address just past the last instruction.
+    JumpedPastEnd { pc: usize, target: i64 },
+    /// The instruction at `pc` would have taken `register` out of the range of an `i64`.
+    RegisterOverflow { pc: usize, register: Register },
+    /// The machine ran `steps` instructions, its limit, without stopping, and would run the
+    /// instruction at `pc` next.
+    StepLimit { pc: usize, steps: usize },
+}
+
+impl fmt::Display for Termination {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Termination::Halted => write!(f, "halted"),
+            Termination::InfiniteLoop { pc } => write!(f, "infinite loop at pc {}", pc),
+            Termination::JumpedBeforeStart { pc, target } => {
+                write!(f, "jumped before the start from pc {} to {}", pc, target)
+            }
+            Termination::JumpedPastEnd { pc, target } => {
+                write!(f, "jumped past the end from pc {} to {}", pc, target)
+            }
+            Termination::RegisterOverflow { pc, register } => {
+                write!(f, "register {} overflowed at pc {}", register, pc)
+            }
+            Termination::StepLimit { pc, steps } => {
+                write!(f, "stopped at pc {} after {} steps", pc, steps)
+            }
+        }
+    }
+}
+
+/// One instruction run by a machine, with every register before and after it ran.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TraceStep {
+    pub pc: usize,
+    pub instruction: Instruction,
+    pub registers_before: [i64; REGISTER_COUNT],
+    pub registers_after: [i64; REGISTER_COUNT],
+}
+
+/// Shows the register the instruction names, which is the accumulator for operations that
+/// don't take one.
+impl fmt::Display for TraceStep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let register = self.instruction.register;
+        write!(
+            f,
+            "{:>5}  {:<12} {} {} -> {}",
+            self.pc,
+            self.instruction.to_string(),
+            register,
+            self.registers_before[register.index()],
+            self.registers_after[register.index()]
+        )
+    }
+}
+
+fn json_array(values: &[i64]) -> String {
+    let values = values
+        .iter()
+        .map(|value| value.to_string())
+        .collect::<Vec<String>>();
+    format!("[{}]", values.join(","))
+}
+
+/// Every instruction a machine ran, in order, and why it stopped.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Trace {
+    pub steps: Vec<TraceStep>,
+    pub termination: Termination,
+}
+
+impl Trace {
+    /// One line per step, followed by the reason the machine stopped.
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        for step in &self.steps {
+            text += &format!("{}\n", step);
+        }
+        text += &format!("{}\n", self.termination);
+        text
+    }
+
+    pub fn to_json(&self) -> String {
+        let steps = self
+            .steps
+            .iter()
+            .map(|step| {
+                let register = match step.instruction.op_code.info().operands {
+                    Operands::RegisterValue | Operands::RegisterOffset => {
+                        format!("\"register\":\"{}\",", step.instruction.register)
+                    }
+                    _ => String::new(),
+                };
+                format!(
+                    "{{\"pc\":{},\"op_code\":\"{}\",{}\"operand\":{},\"registers_before\":{},\"registers_after\":{}}}",
+                    step.pc,
+                    step.instruction.op_code,
+                    register,
+                    step.instruction.operand,
+                    json_array(&step.registers_before),
+                    json_array(&step.registers_after)
+                )
+            })
+            .collect::<Vec<String>>();
+        format!(
+            "{{\"steps\":[{}],\n\"termination\":\"{}\"}}",
+            steps.join(",\n"),
+            self.termination
+        )
+    }
+}
+
+/// Where a jump by `offset` from `pc` lands. This is signed so that jumps before the start
+/// can be told apart from jumps past the end.
+fn jump_target(pc: usize, offset: i32) -> Option<i64> {
+    i64::try_from(pc).ok()?.checked_add(i64::from(offset))
+}
+
+/// How many instructions a machine running a program with conditional jumps runs before giving
+/// up, unless told otherwise.
+pub const DEFAULT_STEP_LIMIT: usize = 1_000_000;
+
+/// Runs a program one instruction at a time, stopping as soon as it would repeat a state.
+///
+/// Without conditional jumps the path through a program doesn't depend on the registers, so
+/// running any instruction twice means a loop, and the machine stops within as many steps as
+/// the program has instructions. Programs with conditional jumps have to remember every state
+/// instead, and could run forever if their registers never repeat, so for those the machine
+/// also stops after a limited number of steps.
+#[derive(Debug, Clone)]
+pub struct Vm {
+    program: Vec<Instruction>,
+    state: State,
+    executed: Vec<bool>,
+    seen: Option<HashSet<State>>,
+    steps: usize,
+    step_limit: usize,
+    termination: Option<Termination>,
+}
+
+impl Vm {
+    pub fn new(program: Vec<Instruction>) -> Self {
+        let termination = if program.is_empty() {
+            Some(Termination::Halted)
+        } else {
+            None
+        };
+        let seen = if program.iter().any(|i| i.op_code.is_conditional()) {
+            Some(vec![State::default()].into_iter().collect())
+        } else {
+            None
+        };
+        Vm {
+            executed: vec![false; program.len()],
+            program,
+            state: State::default(),
+            seen,
+            steps: 0,
+            step_limit: DEFAULT_STEP_LIMIT,
+            termination,
+        }
+    }
+
+    /// Stops the machine once it has run `step_limit` instructions in total. This only applies
+    /// to programs with conditional jumps, since the others can't run for longer than their
+    /// length.
+    pub fn with_step_limit(mut self, step_limit: usize) -> Self {
+        self.step_limit = step_limit;
+        self.check_step_limit();
+        self
+    }
+
+    pub fn program(&self) -> &[Instruction] {
+        &self.program
+    }
+
+    pub fn state(&self) -> State {
+        self.state
+    }
+
+    /// Why the machine stopped, or `None` if it can still run.
+    pub fn termination(&self) -> Option<Termination> {
+        self.termination
+    }
+
+    /// The instruction the machine will run next.
+    pub fn next_instruction(&self) -> Option<&Instruction> {
+        match self.termination {
+            Some(_) => None,
+            None => self.program.get(self.state.pc),
+        }
+    }
+
+    /// Runs the next instruction. Returns why the machine stopped if it stopped before or
+    /// because of this step.
+    pub fn step(&mut self) -> Option<Termination> {
+        let instruction = match self.next_instruction() {
+            Some(instruction) => *instruction,
+            None => return self.termination,
+        };
+        let pc = self.state.pc;
+        let next_pc = instruction.next_pc(&self.state, self.program.len());
+        if let Some((register, change)) = instruction.register_change() {
+            let value = &mut self.state.registers[register.index()];
+            match value.checked_add(change) {
+                Some(sum) => *value = sum,
+                None => {
+                    self.termination = Some(Termination::RegisterOverflow { pc, register });
+                    return self.termination;
+                }
+            }
+        }
+        self.executed[pc] = true;
+        self.steps += 1;
+
+        self.termination = match next_pc {
+            Err(termination) => Some(termination),
+            Ok(next_pc) => {
+                self.state.pc = next_pc;
+                if next_pc == self.program.len() {
+                    Some(Termination::Halted)
+                } else if self.record_state() {
+                    Some(Termination::InfiniteLoop { pc: next_pc })
+                } else {
+                    None
+                }
+            }
+        };
+        self.check_step_limit();
+        self.termination
+    }
+
+    fn check_step_limit(&mut self) {
+        let is_limited = self.seen.is_some();
+        if is_limited && self.termination.is_none() && self.steps >= self.step_limit {
+            self.termination = Some(Termination::StepLimit {
+                pc: self.state.pc,
+                steps: self.steps,
+            });
+        }
+    }
+
+    /// Notes that the machine has reached its current state, returning whether it had been in
+    /// that state before.
+    fn record_state(&mut self) -> bool {
+        match &mut self.seen {
+            Some(seen) => !seen.insert(self.state),
+            None => self.executed[self.state.pc],
+        }
+    }
+
+    /// Runs the next instruction and records what it did, or returns `None` if the machine has
+    /// already stopped.
+    pub fn traced_step(&mut self) -> Option<TraceStep> {
+        let instruction = *self.next_instruction()?;
+        let before = self.state;
+        self.step();
+        Some(TraceStep {
+            pc: before.pc,
+            instruction,
+            registers_before: before.registers,
+            registers_after: self.state.registers,
+        })
+    }
+
+    /// Runs the program until it stops, recording every instruction.
+    pub fn trace(&mut self) -> Trace {
+        let mut steps = vec![];
+        while let Some(step) = self.traced_step() {
+            steps.push(step);
+        }
+        Trace {
+            steps,
+            termination: self.termination.unwrap(),
+        }
+    }
+
+    /// Runs the program until it stops.
+    pub fn run(&mut self) -> Termination {
+        loop {
+            if let Some(termination) = self.step() {
+                return termination;
+            }
+        }
+    }
+
+    /// Runs the program until `predicate` holds for the machine state, which is checked before
+    /// every instruction. Returns why the machine stopped if it stopped first.
+    pub fn run_until<P: FnMut(&State) -> bool>(&mut self, mut predicate: P) -> Option<Termination> {
+        while self.termination.is_none() {
+            if predicate(&self.state) {
+                return None;
+            }
+            self.step();
+        }
+        self.termination
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn program(instructions: &[(OpCode, i32)]) -> Vec<Instruction> {
+        instructions
+            .iter()
+            .map(|(op_code, operand)| Instruction::new(*op_code, *operand))
+            .collect()
+    }
+
+    fn state(pc: usize, accumulator: i64) -> State {
+        State {
+            pc,
+            registers: [accumulator, 0, 0, 0],
+        }
+    }
+
+    #[test]
+    fn stepping() {
+        let mut vm = Vm::new(program(&[
+            (OpCode::Acc, 5),
+            (OpCode::Jmp, 2),
+            (OpCode::Acc, 100),
+            (OpCode::Acc, -2),
+        ]));
+        assert_eq!(vm.next_instruction(), Some(&vm.program()[0]));
+        assert_eq!(vm.step(), None);
+        assert_eq!(vm.state(), state(1, 5));
+        assert_eq!(vm.step(), None);
+        assert_eq!(vm.state(), state(3, 5));
+        assert_eq!(vm.step(), Some(Termination::Halted));
+        assert_eq!(vm.state(), state(4, 3));
+        assert_eq!(vm.next_instruction(), None);
+        assert_eq!(vm.step(), Some(Termination::Halted));
+        assert_eq!(vm.state().accumulator(), 3);
+    }
+
+    #[test]
+    fn terminations() {
+        assert_eq!(Vm::new(vec![]).run(), Termination::Halted);
+
+        let mut vm = Vm::new(program(&[(OpCode::Acc, 1), (OpCode::Jmp, -1)]));
+        assert_eq!(vm.run(), Termination::InfiniteLoop { pc: 0 });
+        assert_eq!(vm.state(), state(0, 1));
+
+        let mut vm = Vm::new(program(&[(OpCode::Nop, 0), (OpCode::Jmp, 1)]));
+        assert_eq!(vm.run(), Termination::Halted);
+        assert_eq!(vm.state().pc, 2);
+    }
+
+    #[test]
+    fn jumps_out_of_bounds() {
+        let mut vm = Vm::new(program(&[(OpCode::Nop, 0), (OpCode::Jmp, -2)]));
+        assert_eq!(
+            vm.run(),
+            Termination::JumpedBeforeStart { pc: 1, target: -1 }
+        );
+        assert_eq!(vm.state().pc, 1);
+
+        let mut vm = Vm::new(program(&[(OpCode::Jmp, i32::MIN)]));
+        assert_eq!(
+            vm.run(),
+            Termination::JumpedBeforeStart {
+                pc: 0,
+                target: i64::from(i32::MIN)
+            }
+        );
+
+        let mut vm = Vm::new(program(&[(OpCode::Jmp, 2)]));
+        assert_eq!(vm.run(), Termination::JumpedPastEnd { pc: 0, target: 2 });
+
+        let mut vm = Vm::new(program(&[(OpCode::Nop, 0), (OpCode::Jmp, i32::MAX)]));
+        assert_eq!(
+            vm.run(),
+            Termination::JumpedPastEnd {
+                pc: 1,
+                target: i64::from(i32::MAX) + 1
+            }
+        );
+        assert_eq!(jump_target(usize::MAX, 1), None);
+    }
+
+    #[test]
+    fn run_until() {
+        let mut vm = Vm::new(program(&[
+            (OpCode::Acc, 1),
+            (OpCode::Acc, 1),
+            (OpCode::Acc, 1),
+            (OpCode::Jmp, -3),
+        ]));
+        assert_eq!(vm.run_until(|state| state.accumulator() == 2), None);
+        assert_eq!(vm.state(), state(2, 2));
+        assert_eq!(vm.run_until(|state| state.pc == 3), None);
+        assert_eq!(vm.state().accumulator(), 3);
+        assert_eq!(
+            vm.run_until(|state| state.accumulator() > 3),
+            Some(Termination::InfiniteLoop { pc: 0 })
+        );
+    }
+
+    #[test]
+    fn tracing() {
+        let mut vm = Vm::new(program(&[
+            (OpCode::Nop, 0),
+            (OpCode::Acc, -3),
+            (OpCode::Jmp, -1),
+        ]));
+        let trace = vm.trace();
+        assert_eq!(trace.steps.len(), 3);
+        assert_eq!(trace.termination, Termination::InfiniteLoop { pc: 1 });
+        assert_eq!(vm.traced_step(), None);
+        assert_eq!(
+            trace.to_text(),
+            "    0  nop +0       acc 0 -> 0
+    1  acc -3       acc 0 -> -3
+    2  jmp -1       acc -3 -> -3
+infinite loop at pc 1
+"
+        );
+        assert_eq!(
+            trace.to_json(),
+            r#"{"steps":[{"pc":0,"op_code":"nop","operand":0,"registers_before":[0,0,0,0],"registers_after":[0,0,0,0]},
+{"pc":1,"op_code":"acc","operand":-3,"registers_before":[0,0,0,0],"registers_after":[-3,0,0,0]},
+{"pc":2,"op_code":"jmp","operand":-1,"registers_before":[-3,0,0,0],"registers_after":[-3,0,0,0]}],
+"termination":"infinite loop at pc 1"}"#
+        );
+
+        let r2 = Register::from_name("r2").unwrap();
+        let trace = Vm::new(vec![
+            Instruction::with_register(OpCode::Add, r2, 7),
+            Instruction::new(OpCode::Acc, 1),
+            Instruction::with_register(OpCode::Jz, r2, 5),
+        ])
+        .trace();
+        assert_eq!(trace.steps[0].registers_after, [0, 0, 7, 0]);
+        assert_eq!(
+            trace.to_text(),
+            "    0  add r2 +7    r2 0 -> 7
+    1  acc +1       acc 0 -> 1
+    2  jz r2 +5     r2 7 -> 7
+halted
+"
+        );
+        assert_eq!(
+            trace.to_json().lines().next().unwrap(),
+            r#"{"steps":[{"pc":0,"op_code":"add","register":"r2","operand":7,"registers_before":[0,0,0,0],"registers_after":[0,0,7,0]},"#
+        );
+    }
+
+    #[test]
+    fn registers_and_conditional_jumps() {
+        let r1 = Register::from_name("r1").unwrap();
+        // counts r1 down from 3, adding 2 to the accumulator each time
+        let mut vm = Vm::new(vec![
+            Instruction::with_register(OpCode::Add, r1, 3),
+            Instruction::with_register(OpCode::Jz, r1, 4),
+            Instruction::new(OpCode::Acc, 2),
+            Instruction::with_register(OpCode::Add, r1, -1),
+            Instruction::new(OpCode::Jmp, -3),
+            Instruction::new(OpCode::Hlt, 0),
+            Instruction::new(OpCode::Acc, 100),
+        ]);
+        assert_eq!(vm.run(), Termination::Halted);
+        assert_eq!(
+            vm.state(),
+            State {
+                pc: 5,
+                registers: [6, 0, 0, 0]
+            }
+        );
+
+        let r2 = Register::from_name("r2").unwrap();
+        let mut vm = Vm::new(vec![
+            Instruction::with_register(OpCode::Jnz, r2, 2),
+            Instruction::new(OpCode::Jmp, -1),
+            Instruction::new(OpCode::Nop, 0),
+        ]);
+        assert_eq!(vm.run(), Termination::InfiniteLoop { pc: 0 });
+        assert_eq!(Register::from_name("r4"), None);
+        assert_eq!(vm.program()[0].to_string(), "jnz r2 +2");
+        assert_eq!(Instruction::new(OpCode::Hlt, 0).to_string(), "hlt");
+    }
+
+    #[test]
+    fn step_limit() {
+        let r1 = Register::from_name("r1").unwrap();
+        // r1 never repeats, so only the step limit stops this
+        let counter = vec![
+            Instruction::with_register(OpCode::Add, r1, 1),
+            Instruction::with_register(OpCode::Jnz, r1, -1),
+        ];
+        let mut vm = Vm::new(counter.clone());
+        assert_eq!(
+            vm.run(),
+            Termination::StepLimit {
+                pc: 0,
+                steps: DEFAULT_STEP_LIMIT
+            }
+        );
+        assert_eq!(vm.state().registers[1], DEFAULT_STEP_LIMIT as i64 / 2);
+
+        let mut vm = Vm::new(counter.clone()).with_step_limit(3);
+        assert_eq!(vm.run(), Termination::StepLimit { pc: 1, steps: 3 });
+        assert_eq!(vm.next_instruction(), None);
+        assert_eq!(vm.trace().steps, vec![]);
+        assert_eq!(
+            Vm::new(counter).with_step_limit(0).termination(),
+            Some(Termination::StepLimit { pc: 0, steps: 0 })
+        );
+
+        let mut vm = Vm::new(program(&[(OpCode::Acc, 1), (OpCode::Acc, 1)])).with_step_limit(2);
+        assert_eq!(vm.run(), Termination::Halted);
+
+        // without conditional jumps the program's length bounds the run instead
+        let mut vm = Vm::new(program(&[(OpCode::Nop, 0); 3])).with_step_limit(1);
+        assert_eq!(vm.run(), Termination::Halted);
+        let mut vm = Vm::new(program(&[(OpCode::Nop, 0), (OpCode::Jmp, -1)])).with_step_limit(0);
+        assert_eq!(vm.run(), Termination::InfiniteLoop { pc: 0 });
+    }
+
+    #[test]
+    fn register_overflow() {
+        let r3 = Register::from_name("r3").unwrap();
+        let mut vm = Vm::new(vec![
+            Instruction::with_register(OpCode::Add, r3, -1),
+            Instruction::with_register(OpCode::Jnz, r3, 0),
+        ]);
+        vm.state.registers[r3.index()] = i64::MIN + 1;
+        assert_eq!(vm.step(), None);
+        assert_eq!(vm.step(), Some(Termination::InfiniteLoop { pc: 1 }));
+
+        let mut vm = Vm::new(program(&[(OpCode::Nop, 0), (OpCode::Acc, i32::MAX)]));
+        vm.state.registers[0] = i64::MAX - i64::from(i32::MAX) + 1;
+        assert_eq!(
+            vm.run(),
+            Termination::RegisterOverflow {
+                pc: 1,
+                register: Register::ACC
+            }
+        );
+        assert_eq!(vm.state().pc, 1);
+        assert_eq!(
+            vm.termination().unwrap().to_string(),
+            "register acc overflowed at pc 1"
+        );
+    }
+}
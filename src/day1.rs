@@ -1,37 +1,287 @@
 use aoc_runner_derive::{aoc, aoc_generator};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
 use std::num::ParseIntError;
 
+/// A set of entries that add up to the target, identified by their positions in the input.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Combination {
+    pub indices: Vec<usize>,
+    pub entries: Vec<i32>,
+}
+
+impl Combination {
+    pub fn product(&self) -> i64 {
+        self.entries.iter().map(|e| *e as i64).product()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KSumError {
+    NoSolution { k: usize, target: i32 },
+    MultipleSolutions { k: usize, target: i32, count: usize },
+}
+
+impl fmt::Display for KSumError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KSumError::NoSolution { k, target } => {
+                write!(f, "no {} entries add up to {}", k, target)
+            }
+            KSumError::MultipleSolutions { k, target, count } => write!(
+                f,
+                "expected one set of {} entries adding up to {}, found {}",
+                k, target, count
+            ),
+        }
+    }
+}
+
+impl Error for KSumError {}
+
 #[aoc_generator(day1)]
 pub fn get_values(input: &str) -> Result<Vec<i32>, ParseIntError> {
     input.lines().map(|l| l.parse::<i32>()).collect()
 }
 
 #[aoc(day1, part1)]
-pub fn part1(inputs: &[i32]) -> i32 {
-    let mut result: Option<i32> = None;
-    for (index, i) in inputs.iter().enumerate() {
-        for j in inputs.iter().skip(index + 1) {
-            if i + j == 2020 {
-                result = Some(i * j);
-            }
-        }
+pub fn part1(inputs: &[i32]) -> Result<i64, KSumError> {
+    find_unique_k_sum(inputs, 2, 2020).map(|c| c.product())
+}
+
+#[aoc(day1, part2)]
+pub fn part2(inputs: &[i32]) -> Result<i64, KSumError> {
+    find_unique_k_sum(inputs, 3, 2020).map(|c| c.product())
+}
+
+/// Like `find_all_k_sums`, but treats more than one matching combination as an error.
+pub fn find_unique_k_sum(inputs: &[i32], k: usize, target: i32) -> Result<Combination, KSumError> {
+    let mut combinations = find_all_k_sums(inputs, k, target)?;
+    if combinations.len() > 1 {
+        return Err(KSumError::MultipleSolutions {
+            k,
+            target,
+            count: combinations.len(),
+        });
+    }
+    Ok(combinations.remove(0))
+}
+
+/// Finds every set of `k` distinct positions in `inputs` whose entries add up to `target`.
+///
+/// Positions rather than values are what make a combination distinct, so repeated values in the
+/// input each take part in their own combinations. Results are ordered by position.
+pub fn find_all_k_sums(
+    inputs: &[i32],
+    k: usize,
+    target: i32,
+) -> Result<Vec<Combination>, KSumError> {
+    let combinations = k_sums(inputs, k, target, None);
+    if combinations.is_empty() {
+        return Err(KSumError::NoSolution { k, target });
     }
+    Ok(combinations)
+}
 
-    result.unwrap()
+/// Finds the first set of `k` distinct positions in `inputs`, in position order, whose entries
+/// add up to `target`. The search stops as soon as it finds one.
+pub fn find_k_sum(inputs: &[i32], k: usize, target: i32) -> Option<Combination> {
+    k_sums(inputs, k, target, Some(1)).pop()
 }
 
-#[aoc(day1, part2)]
-pub fn part2(inputs: &[i32]) -> i32 {
-    let mut result: Option<i32> = None;
-    for (i_index, i) in inputs.iter().enumerate() {
-        for (j_index, j) in inputs.iter().skip(i_index + 1).enumerate() {
-            for k in inputs.iter().skip(j_index) {
-                if i + j + k == 2020 {
-                    result = Some(i * j * k);
+/// Combinations in position order, stopping once there are `limit` of them.
+fn k_sums(inputs: &[i32], k: usize, target: i32, limit: Option<usize>) -> Vec<Combination> {
+    let mut found = vec![];
+    collect_k_sums(inputs, 0, k, target as i64, limit, &mut vec![], &mut found);
+    found
+        .into_iter()
+        .map(|indices| Combination {
+            entries: indices.iter().map(|idx| inputs[*idx]).collect(),
+            indices,
+        })
+        .collect()
+}
+
+/// Adds the combinations that extend `chosen` to `found`, in position order, until `found`
+/// holds `limit` of them.
+fn collect_k_sums(
+    inputs: &[i32],
+    start: usize,
+    k: usize,
+    target: i64,
+    limit: Option<usize>,
+    chosen: &mut Vec<usize>,
+    found: &mut Vec<Vec<usize>>,
+) {
+    let is_full = |found: &Vec<Vec<usize>>| limit == Some(found.len());
+    let add = |found: &mut Vec<Vec<usize>>, indices: &[usize]| {
+        let mut combination = chosen.clone();
+        combination.extend_from_slice(indices);
+        found.push(combination);
+    };
+    if is_full(found) {
+        return;
+    }
+
+    match k {
+        0 => {
+            if target == 0 {
+                add(found, &[]);
+            }
+        }
+        1 => {
+            for (idx, value) in inputs.iter().enumerate().skip(start) {
+                if *value as i64 == target {
+                    add(found, &[idx]);
+                    if is_full(found) {
+                        return;
+                    }
+                }
+            }
+        }
+        2 => {
+            let mut positions: HashMap<i64, Vec<usize>> = HashMap::new();
+            for (idx, value) in inputs.iter().enumerate().skip(start) {
+                positions.entry(*value as i64).or_default().push(idx);
+            }
+            for (first, value) in inputs.iter().enumerate().skip(start) {
+                let partners = match positions.get(&(target - *value as i64)) {
+                    Some(partners) => partners,
+                    None => continue,
+                };
+                let after = partners.partition_point(|partner| *partner <= first);
+                for second in &partners[after..] {
+                    add(found, &[first, *second]);
+                    if is_full(found) {
+                        return;
+                    }
                 }
             }
         }
+        _ => {
+            for idx in start..inputs.len() {
+                chosen.push(idx);
+                collect_k_sums(
+                    inputs,
+                    idx + 1,
+                    k - 1,
+                    target - inputs[idx] as i64,
+                    limit,
+                    chosen,
+                    found,
+                );
+                chosen.pop();
+                if is_full(found) {
+                    return;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: [i32; 6] = [1721, 979, 366, 299, 675, 1456];
+
+    fn entries(combination: Option<Combination>) -> Option<Vec<i32>> {
+        combination.map(|c| c.entries)
+    }
+
+    #[test]
+    fn example_pair() {
+        assert_eq!(
+            entries(find_k_sum(&EXAMPLE, 2, 2020)),
+            Some(vec![1721, 299])
+        );
+        assert_eq!(part1(&EXAMPLE), Ok(514579));
+    }
+
+    #[test]
+    fn example_triple() {
+        assert_eq!(
+            entries(find_k_sum(&EXAMPLE, 3, 2020)),
+            Some(vec![979, 366, 675])
+        );
+        assert_eq!(part2(&EXAMPLE), Ok(241861950));
+    }
+
+    #[test]
+    fn other_targets_and_sizes() {
+        assert_eq!(entries(find_k_sum(&EXAMPLE, 1, 366)), Some(vec![366]));
+        assert_eq!(
+            find_k_sum(&EXAMPLE, 4, 299 + 366 + 675 + 979),
+            Some(Combination {
+                indices: vec![1, 2, 3, 4],
+                entries: vec![979, 366, 299, 675]
+            })
+        );
+        assert_eq!(entries(find_k_sum(&EXAMPLE, 0, 0)), Some(vec![]));
     }
 
-    result.unwrap()
+    #[test]
+    fn entries_are_not_reused() {
+        assert_eq!(find_k_sum(&[1010, 5], 2, 2020), None);
+        assert_eq!(
+            entries(find_k_sum(&[1010, 1010, 5], 2, 2020)),
+            Some(vec![1010, 1010])
+        );
+    }
+
+    #[test]
+    fn stops_at_the_first_combination() {
+        let pairs = vec![1010; 2000];
+        assert_eq!(
+            find_k_sum(&pairs, 2, 2020).map(|c| c.indices),
+            Some(vec![0, 1])
+        );
+        let zeros = vec![0; 300];
+        assert_eq!(
+            find_k_sum(&zeros, 3, 0).map(|c| c.indices),
+            Some(vec![0, 1, 2])
+        );
+    }
+
+    #[test]
+    fn all_combinations_by_index() {
+        assert_eq!(
+            find_all_k_sums(&[1010, 1010, 1010, 2020, 0], 2, 2020),
+            Ok(vec![
+                Combination {
+                    indices: vec![0, 1],
+                    entries: vec![1010, 1010]
+                },
+                Combination {
+                    indices: vec![0, 2],
+                    entries: vec![1010, 1010]
+                },
+                Combination {
+                    indices: vec![1, 2],
+                    entries: vec![1010, 1010]
+                },
+                Combination {
+                    indices: vec![3, 4],
+                    entries: vec![2020, 0]
+                },
+            ])
+        );
+    }
+
+    #[test]
+    fn no_solution_is_an_error() {
+        assert_eq!(
+            part1(&[1, 2, 3]),
+            Err(KSumError::NoSolution { k: 2, target: 2020 })
+        );
+        assert_eq!(
+            find_unique_k_sum(&[1000, 1020, 1010, 1010], 2, 2020),
+            Err(KSumError::MultipleSolutions {
+                k: 2,
+                target: 2020,
+                count: 2
+            })
+        );
+    }
 }
@@ -15,7 +15,7 @@ pub fn part1(_: &[usize]) -> usize {
 
 #[aoc(day10, part2)]
 pub fn part2(raw: &[usize]) -> u64 {
-    let mut copy = raw.iter().cloned().collect::<Vec<usize>>();
+    let mut copy = raw.to_vec();
     let max = raw.iter().max().unwrap();
     copy.push(0);
     copy.push(*max);
@@ -43,7 +43,7 @@ fn get_combinations_count(
     let mut accumulator = 0;
 
     while adapters[next_index] <= current_value + 3 && next_index < adapters.len() - 1 {
-        accumulator += get_combinations_count(&adapters, next_index, memo);
+        accumulator += get_combinations_count(adapters, next_index, memo);
         next_index += 1;
     }
 
@@ -70,7 +70,7 @@ pub fn get_values(input: &str) -> SeatingArea {
 
 #[aoc(day11, part1)]
 pub fn part1(starting_setup: &SeatingArea) -> usize {
-    let mut result = evaluate_round(&starting_setup, 4, get_num_adjacent_occupied);
+    let mut result = evaluate_round(starting_setup, 4, get_num_adjacent_occupied);
     loop {
         result = evaluate_round(
             &SeatingArea {
@@ -92,7 +92,7 @@ pub fn part1(starting_setup: &SeatingArea) -> usize {
 
 #[aoc(day11, part2)]
 pub fn part2(starting_setup: &SeatingArea) -> usize {
-    let mut result = evaluate_round(&starting_setup, 5, get_num_visible_occupied);
+    let mut result = evaluate_round(starting_setup, 5, get_num_visible_occupied);
     loop {
         result = evaluate_round(
             &SeatingArea {
@@ -132,7 +132,7 @@ fn evaluate_round(
                 continue;
             }
 
-            let num_adjacent_occupied = adjacency_evaluator(&starting_setup, row, col);
+            let num_adjacent_occupied = adjacency_evaluator(starting_setup, row, col);
 
             let updated_space = updated_area.entry(Space { row, col });
 
@@ -263,11 +263,8 @@ mod part_1_tests {
 
         let (result, made_update) = evaluate_round(&seating_area, 4, get_num_adjacent_occupied);
 
-        assert_eq!(made_update, true);
-        assert_eq!(
-            result.get(&Space { row: 0, col: 0 }).unwrap().occupied,
-            true
-        );
+        assert!(made_update);
+        assert!(result.get(&Space { row: 0, col: 0 }).unwrap().occupied);
 
         let (result_2, made_update_2) = evaluate_round(
             &SeatingArea {
@@ -278,11 +275,8 @@ mod part_1_tests {
             4,
             get_num_adjacent_occupied,
         );
-        assert_eq!(made_update_2, false);
-        assert_eq!(
-            result_2.get(&Space { row: 0, col: 0 }).unwrap().occupied,
-            true
-        );
+        assert!(!made_update_2);
+        assert!(result_2.get(&Space { row: 0, col: 0 }).unwrap().occupied);
     }
 
     #[test]
@@ -306,11 +300,8 @@ mod part_1_tests {
             get_num_adjacent_occupied,
         );
 
-        assert_eq!(made_update, false);
-        assert_eq!(
-            result.get(&Space { row: 0, col: 0 }).unwrap().occupied,
-            false
-        );
+        assert!(!made_update);
+        assert!(!result.get(&Space { row: 0, col: 0 }).unwrap().occupied);
     }
 
     #[test]
@@ -337,10 +328,10 @@ mod part_1_tests {
             get_num_adjacent_occupied,
         );
 
-        assert_eq!(made_update, true);
+        assert!(made_update);
         for row in 0..=1 {
             for col in 0..1 {
-                assert_eq!(result.get(&Space { row, col }).unwrap().occupied, true);
+                assert!(result.get(&Space { row, col }).unwrap().occupied);
 
                 assert_eq!(
                     get_num_adjacent_occupied(
@@ -382,10 +373,10 @@ mod part_1_tests {
             get_num_adjacent_occupied,
         );
 
-        assert_eq!(made_update, true);
+        assert!(made_update);
         for row in 0..=2 {
             for col in 0..2 {
-                assert_eq!(result.get(&Space { row, col }).unwrap().occupied, true);
+                assert!(result.get(&Space { row, col }).unwrap().occupied);
             }
         }
 
@@ -81,7 +81,7 @@ impl Instruction {
 
 #[aoc_generator(day12)]
 pub fn get_values(input: &str) -> Vec<Instruction> {
-    input.lines().map(|v| Instruction::from_str(v)).collect()
+    input.lines().map(Instruction::from_str).collect()
 }
 
 #[aoc(day12, part1)]
@@ -58,19 +58,19 @@ pub fn part2((_, bus_lines_raw): &(u32, String)) -> u64 {
     find_solution(&schedules)
 }
 
+// `u64::is_multiple_of` would need Rust 1.87
+#[allow(clippy::manual_is_multiple_of)]
 fn find_solution(schedules_input: &[BusSchedule]) -> u64 {
     let mut schedules = schedules_input.to_owned().clone();
     schedules[0].matched = true;
     let mut step_size = schedules[0].time;
-    let mut current_time = step_size as u64;
+    let mut current_time = step_size;
 
     loop {
-        for mut schedule in schedules.iter_mut() {
-            if !schedule.matched
-                && (current_time + schedule.offset as u64) % schedule.time as u64 == 0
-            {
+        for schedule in schedules.iter_mut() {
+            if !schedule.matched && (current_time + schedule.offset as u64) % schedule.time == 0 {
                 schedule.matched = true;
-                step_size = step_size * schedule.time;
+                step_size *= schedule.time;
             }
         }
 
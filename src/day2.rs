@@ -1,75 +1,690 @@
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
 use aoc_runner_derive::{aoc, aoc_generator};
-use regex::Regex;
+use unicode_segmentation::UnicodeSegmentation;
 
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct PasswordRecord {
-    minimum_count: usize,
-    maximum_count: usize,
-    letter: char,
-    password: String,
+    pub minimum_count: usize,
+    pub maximum_count: usize,
+    /// A single grapheme cluster, so accented or combined letters compare as one character.
+    pub letter: String,
+    pub password: String,
 }
 
-#[aoc_generator(day2)]
-pub fn get_values(input: &str) -> Vec<PasswordRecord> {
-    lazy_static! {
-        static ref PARSING_EXPR: Regex = Regex::new(
-            r"^(?P<minCount>\d+)-(?P<maxCount>\d+) (?P<letter>[a-z]): (?P<password>[a-z]+)$"
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseRecordErrorKind {
+    InvalidCount,
+    MissingSeparator(char),
+    InvalidLetter,
+    InvalidPassword,
+}
+
+/// Describes where a password record failed to parse. `column` is 1-based and counts grapheme
+/// clusters.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseRecordError {
+    pub line: usize,
+    pub column: usize,
+    pub text: String,
+    pub kind: ParseRecordErrorKind,
+}
+
+impl ParseRecordError {
+    fn new(line: &str, offset: usize, text: &str, kind: ParseRecordErrorKind) -> Self {
+        ParseRecordError {
+            line: 1,
+            column: line[..offset].graphemes(true).count() + 1,
+            text: text.to_string(),
+            kind,
+        }
+    }
+
+    fn at_line(self, line: usize) -> Self {
+        ParseRecordError { line, ..self }
+    }
+}
+
+impl fmt::Display for ParseRecordError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let expected = match self.kind {
+            ParseRecordErrorKind::InvalidCount => "expected a count".to_string(),
+            ParseRecordErrorKind::MissingSeparator(c) => format!("expected '{}'", c),
+            ParseRecordErrorKind::InvalidLetter => "expected a single letter".to_string(),
+            ParseRecordErrorKind::InvalidPassword => "expected a password".to_string(),
+        };
+        write!(
+            f,
+            "line {}, column {}: {}, found {:?}",
+            self.line, self.column, expected, self.text
         )
-        .unwrap();
     }
+}
+
+impl Error for ParseRecordError {}
+
+/// Splits `line[offset..]` at the next `separator`, returning the field and the offset just past
+/// the separator.
+fn take_field(
+    line: &str,
+    offset: usize,
+    separator: char,
+) -> Result<(&str, usize), ParseRecordError> {
+    let rest = &line[offset..];
+    match rest.find(separator) {
+        Some(idx) => Ok((&rest[..idx], offset + idx + separator.len_utf8())),
+        None => Err(ParseRecordError::new(
+            line,
+            offset,
+            rest,
+            ParseRecordErrorKind::MissingSeparator(separator),
+        )),
+    }
+}
+
+fn parse_count(line: &str, offset: usize, field: &str) -> Result<usize, ParseRecordError> {
+    field
+        .parse::<usize>()
+        .map_err(|_| ParseRecordError::new(line, offset, field, ParseRecordErrorKind::InvalidCount))
+}
+
+/// Parses a single `min-max letter: password` line. A trailing `\r` is ignored.
+impl FromStr for PasswordRecord {
+    type Err = ParseRecordError;
+
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        let line = line.strip_suffix('\r').unwrap_or(line);
+
+        let (minimum, maximum_offset) = take_field(line, 0, '-')?;
+        let minimum_count = parse_count(line, 0, minimum)?;
+
+        let (maximum, letter_offset) = take_field(line, maximum_offset, ' ')?;
+        let maximum_count = parse_count(line, maximum_offset, maximum)?;
+
+        let (letter, space_offset) = take_field(line, letter_offset, ':')?;
+        let mut letter_graphemes = letter.graphemes(true);
+        let letter = match (letter_graphemes.next(), letter_graphemes.next()) {
+            (Some(g), None) if !g.trim().is_empty() => g.to_string(),
+            _ => {
+                return Err(ParseRecordError::new(
+                    line,
+                    letter_offset,
+                    letter,
+                    ParseRecordErrorKind::InvalidLetter,
+                ))
+            }
+        };
+
+        let (gap, password_offset) = take_field(line, space_offset, ' ')?;
+        if !gap.is_empty() {
+            return Err(ParseRecordError::new(
+                line,
+                space_offset,
+                gap,
+                ParseRecordErrorKind::MissingSeparator(' '),
+            ));
+        }
+
+        let password = &line[password_offset..];
+        if password.is_empty() {
+            return Err(ParseRecordError::new(
+                line,
+                password_offset,
+                password,
+                ParseRecordErrorKind::InvalidPassword,
+            ));
+        }
+
+        Ok(PasswordRecord {
+            minimum_count,
+            maximum_count,
+            letter,
+            password: password.to_string(),
+        })
+    }
+}
+
+#[aoc_generator(day2)]
+pub fn get_values(input: &str) -> Result<Vec<PasswordRecord>, ParseRecordError> {
     input
         .lines()
-        .map(|line| {
-            let caps = PARSING_EXPR.captures(line).unwrap();
-            PasswordRecord {
-                minimum_count: caps["minCount"].parse::<usize>().unwrap(),
-                maximum_count: caps["maxCount"].parse::<usize>().unwrap(),
-                letter: caps["letter"].chars().next().unwrap(),
-                password: caps["password"].to_string(),
-            }
+        .enumerate()
+        .map(|(idx, line)| {
+            line.parse::<PasswordRecord>()
+                .map_err(|e| e.at_line(idx + 1))
         })
         .collect()
 }
 
-#[aoc(day2, part1)]
-pub fn part1(inputs: &[PasswordRecord]) -> usize {
+/// A rule that decides whether a password satisfies the policy written alongside it.
+pub trait PasswordPolicy {
+    fn name(&self) -> String;
+
+    /// Explains why `record` breaks this policy, or returns `None` if it complies.
+    fn violation(&self, record: &PasswordRecord) -> Option<String>;
+
+    fn is_valid(&self, record: &PasswordRecord) -> bool {
+        self.violation(record).is_none()
+    }
+}
+
+/// The letter must appear between `minimum_count` and `maximum_count` times, inclusive.
+pub struct CountInRange;
+
+impl PasswordPolicy for CountInRange {
+    fn name(&self) -> String {
+        "count-in-range".to_string()
+    }
+
+    fn violation(&self, record: &PasswordRecord) -> Option<String> {
+        let count_of_letter = record
+            .password
+            .graphemes(true)
+            .filter(|l| *l == record.letter)
+            .count();
+        if count_of_letter < record.minimum_count {
+            Some(format!(
+                "letter '{}' appears {} times, min {}",
+                record.letter, count_of_letter, record.minimum_count
+            ))
+        } else if count_of_letter > record.maximum_count {
+            Some(format!(
+                "letter '{}' appears {} times, max {}",
+                record.letter, count_of_letter, record.maximum_count
+            ))
+        } else {
+            None
+        }
+    }
+}
+
+/// The letter must appear at exactly one of the two positions. Positions are 1-based unless
+/// `zero_based` is set, and a position past the end of the password never holds the letter.
+pub struct ExactlyOnePosition {
+    pub zero_based: bool,
+}
+
+impl PasswordPolicy for ExactlyOnePosition {
+    fn name(&self) -> String {
+        if self.zero_based {
+            "exactly-one-position-0".to_string()
+        } else {
+            "exactly-one-position".to_string()
+        }
+    }
+
+    fn violation(&self, record: &PasswordRecord) -> Option<String> {
+        let first_index_is_letter = letter_at(record, record.minimum_count, self.zero_based);
+        let last_index_is_letter = letter_at(record, record.maximum_count, self.zero_based);
+        let positions = match (first_index_is_letter, last_index_is_letter) {
+            (Ok(first), Ok(last)) => (first, last),
+            (Err(reason), _) | (_, Err(reason)) => return Some(reason),
+        };
+        match positions {
+            (true, true) => Some(format!(
+                "letter '{}' is at both positions {} and {}",
+                record.letter, record.minimum_count, record.maximum_count
+            )),
+            (false, false) => Some(format!(
+                "letter '{}' is at neither position {} nor {}",
+                record.letter, record.minimum_count, record.maximum_count
+            )),
+            _ => None,
+        }
+    }
+}
+
+/// The letter must appear at both of the listed 1-based positions.
+pub struct AllPositions;
+
+impl PasswordPolicy for AllPositions {
+    fn name(&self) -> String {
+        "all-positions".to_string()
+    }
+
+    fn violation(&self, record: &PasswordRecord) -> Option<String> {
+        [record.minimum_count, record.maximum_count]
+            .iter()
+            .find_map(|position| match letter_at(record, *position, false) {
+                Ok(true) => None,
+                Ok(false) => Some(format!(
+                    "letter '{}' is not at position {}",
+                    record.letter, position
+                )),
+                Err(reason) => Some(reason),
+            })
+    }
+}
+
+/// The password must contain at least this many different letters, regardless of the record.
+pub struct DistinctLetters(pub usize);
+
+impl PasswordPolicy for DistinctLetters {
+    fn name(&self) -> String {
+        format!("distinct-letters={}", self.0)
+    }
+
+    fn violation(&self, record: &PasswordRecord) -> Option<String> {
+        let mut letters = record.password.graphemes(true).collect::<Vec<&str>>();
+        letters.sort_unstable();
+        letters.dedup();
+        if letters.len() < self.0 {
+            Some(format!(
+                "password has {} distinct letters, min {}",
+                letters.len(),
+                self.0
+            ))
+        } else {
+            None
+        }
+    }
+}
+
+/// Whether the record's letter is at `position`. A position outside the password is a violation
+/// in itself, so it's returned as the reason instead.
+fn letter_at(record: &PasswordRecord, position: usize, zero_based: bool) -> Result<bool, String> {
+    let letters = record.password.graphemes(true).collect::<Vec<&str>>();
+    let index = if zero_based {
+        position
+    } else {
+        position
+            .checked_sub(1)
+            .ok_or_else(|| "position 0 is before the start of the password".to_string())?
+    };
+    match letters.get(index) {
+        Some(letter) => Ok(*letter == record.letter),
+        None => Err(format!(
+            "position {} is past the end of a {}-letter password",
+            position,
+            letters.len()
+        )),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownPolicyError(pub String);
+
+impl fmt::Display for UnknownPolicyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown password policy {:?}", self.0)
+    }
+}
+
+impl Error for UnknownPolicyError {}
+
+/// Looks up a policy by the name it reports, e.g. `count-in-range` or `distinct-letters=5`.
+pub fn policy_from_name(name: &str) -> Result<Box<dyn PasswordPolicy>, UnknownPolicyError> {
+    let unknown = || UnknownPolicyError(name.to_string());
+    let policy: Box<dyn PasswordPolicy> = match name.trim() {
+        "count-in-range" => Box::new(CountInRange),
+        "exactly-one-position" => Box::new(ExactlyOnePosition { zero_based: false }),
+        "exactly-one-position-0" => Box::new(ExactlyOnePosition { zero_based: true }),
+        "all-positions" => Box::new(AllPositions),
+        other => match other.strip_prefix("distinct-letters=") {
+            Some(n) => Box::new(DistinctLetters(n.parse().map_err(|_| unknown())?)),
+            None => return Err(unknown()),
+        },
+    };
+    Ok(policy)
+}
+
+/// Parses a comma-separated list of policy names.
+pub fn policies_from_names(
+    names: &str,
+) -> Result<Vec<Box<dyn PasswordPolicy>>, UnknownPolicyError> {
+    names.split(',').map(policy_from_name).collect()
+}
+
+/// Counts the records that satisfy every one of `policies`.
+pub fn count_valid(inputs: &[PasswordRecord], policies: &[Box<dyn PasswordPolicy>]) -> usize {
     inputs
         .iter()
-        .filter(|record| {
-            let count_of_letter = record
-                .password
-                .chars()
-                .filter(|l| *l == record.letter)
-                .collect::<Vec<char>>()
-                .len();
-            return count_of_letter >= record.minimum_count
-                && count_of_letter <= record.maximum_count;
-        })
-        .collect::<Vec<&PasswordRecord>>()
-        .len()
+        .filter(|record| policies.iter().all(|policy| policy.is_valid(record)))
+        .count()
 }
 
-#[aoc(day2, part2)]
-pub fn part2(inputs: &[PasswordRecord]) -> usize {
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Violation {
+    pub policy: String,
+    pub reason: String,
+}
+
+/// The outcome of checking one record against a set of policies. `line` is 1-based.
+#[derive(Debug)]
+pub struct RecordReport<'a> {
+    pub line: usize,
+    pub record: &'a PasswordRecord,
+    pub violations: Vec<Violation>,
+}
+
+impl RecordReport<'_> {
+    pub fn is_valid(&self) -> bool {
+        self.violations.is_empty()
+    }
+}
+
+/// Checks every record against every policy, keeping each violation rather than stopping at the
+/// first one.
+pub fn validate_records<'a>(
+    inputs: &'a [PasswordRecord],
+    policies: &[Box<dyn PasswordPolicy>],
+) -> Vec<RecordReport<'a>> {
     inputs
         .iter()
-        .filter(|record| {
-            let first_index_is_letter = record
-                .password
-                .chars()
-                .skip(record.minimum_count - 1)
-                .next()
-                .unwrap()
-                == record.letter;
-            let last_index_is_letter = record
-                .password
-                .chars()
-                .skip(record.maximum_count - 1)
-                .next()
-                .unwrap()
-                == record.letter;
-            // ^ is xor
-            return first_index_is_letter ^ last_index_is_letter;
+        .enumerate()
+        .map(|(idx, record)| RecordReport {
+            line: idx + 1,
+            record,
+            violations: policies
+                .iter()
+                .filter_map(|policy| {
+                    policy.violation(record).map(|reason| Violation {
+                        policy: policy.name(),
+                        reason,
+                    })
+                })
+                .collect(),
+        })
+        .collect()
+}
+
+/// Renders one row per violation, and one row with empty policy and reason for a valid record.
+pub fn report_csv(reports: &[RecordReport]) -> String {
+    let mut output = "line,minimum_count,maximum_count,letter,password,policy,reason\n".to_string();
+    for report in reports {
+        let record = report.record;
+        let prefix = format!(
+            "{},{},{},{},{}",
+            report.line,
+            record.minimum_count,
+            record.maximum_count,
+            csv_field(&record.letter),
+            csv_field(&record.password)
+        );
+        if report.violations.is_empty() {
+            output.push_str(&format!("{},,\n", prefix));
+        }
+        for violation in &report.violations {
+            output.push_str(&format!(
+                "{},{},{}\n",
+                prefix,
+                csv_field(&violation.policy),
+                csv_field(&violation.reason)
+            ));
+        }
+    }
+    output
+}
+
+/// Renders the reports as a JSON array with one object per record.
+pub fn report_json(reports: &[RecordReport]) -> String {
+    let records = reports
+        .iter()
+        .map(|report| {
+            let violations = report
+                .violations
+                .iter()
+                .map(|violation| {
+                    format!(
+                        "{{\"policy\":{},\"reason\":{}}}",
+                        json_string(&violation.policy),
+                        json_string(&violation.reason)
+                    )
+                })
+                .collect::<Vec<String>>()
+                .join(",");
+            format!(
+                "{{\"line\":{},\"minimum_count\":{},\"maximum_count\":{},\"letter\":{},\"password\":{},\"valid\":{},\"violations\":[{}]}}",
+                report.line,
+                report.record.minimum_count,
+                report.record.maximum_count,
+                json_string(&report.record.letter),
+                json_string(&report.record.password),
+                report.is_valid(),
+                violations
+            )
         })
-        .collect::<Vec<&PasswordRecord>>()
-        .len()
+        .collect::<Vec<String>>();
+    format!("[{}]", records.join(",\n"))
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn json_string(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len() + 2);
+    escaped.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+/// Like `validate_records`, but keeps only the records that break at least one policy.
+pub fn failing_reports<'a>(
+    inputs: &'a [PasswordRecord],
+    policies: &[Box<dyn PasswordPolicy>],
+) -> Vec<RecordReport<'a>> {
+    validate_records(inputs, policies)
+        .into_iter()
+        .filter(|report| !report.is_valid())
+        .collect()
+}
+
+#[aoc(day2, part1)]
+pub fn part1(inputs: &[PasswordRecord]) -> usize {
+    count_valid(inputs, &[Box::new(CountInRange)])
+}
+
+#[aoc(day2, part2)]
+pub fn part2(inputs: &[PasswordRecord]) -> usize {
+    count_valid(
+        inputs,
+        &[Box::new(ExactlyOnePosition { zero_based: false })],
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_example() {
+        let records = get_values("1-3 a: abcde\n1-3 b: cdefg\r\n2-9 c: ccccccccc").unwrap();
+        assert_eq!(
+            records[0],
+            PasswordRecord {
+                minimum_count: 1,
+                maximum_count: 3,
+                letter: "a".to_string(),
+                password: "abcde".to_string(),
+            }
+        );
+        assert_eq!(records[1].password, "cdefg");
+        assert_eq!(part1(&records), 2);
+        assert_eq!(part2(&records), 1);
+    }
+
+    #[test]
+    fn reports_line_and_column() {
+        let err = get_values("1-3 a: abcde\n1-3 bb: cdefg").err().unwrap();
+        assert_eq!(
+            err,
+            ParseRecordError {
+                line: 2,
+                column: 5,
+                text: "bb".to_string(),
+                kind: ParseRecordErrorKind::InvalidLetter,
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "line 2, column 5: expected a single letter, found \"bb\""
+        );
+    }
+
+    #[test]
+    fn reports_missing_separator() {
+        let err = "1-3 a abcde".parse::<PasswordRecord>().err().unwrap();
+        assert_eq!(err.kind, ParseRecordErrorKind::MissingSeparator(':'));
+        assert_eq!(err.column, 5);
+        assert_eq!(err.text, "a abcde");
+
+        let err = "x-3 a: abcde".parse::<PasswordRecord>().err().unwrap();
+        assert_eq!(err.kind, ParseRecordErrorKind::InvalidCount);
+        assert_eq!(err.column, 1);
+    }
+
+    #[test]
+    fn policies_by_name() {
+        let records = get_values("1-3 a: abcde\n1-3 b: cdefg\n2-9 c: ccccccccc").unwrap();
+        let policies = policies_from_names("count-in-range,distinct-letters=2").unwrap();
+        assert_eq!(count_valid(&records, &policies), 1);
+
+        for name in &[
+            "count-in-range",
+            "exactly-one-position",
+            "exactly-one-position-0",
+            "all-positions",
+            "distinct-letters=3",
+        ] {
+            assert_eq!(policy_from_name(name).unwrap().name(), *name);
+        }
+        assert_eq!(
+            policy_from_name("distinct-letters=x").err(),
+            Some(UnknownPolicyError("distinct-letters=x".to_string()))
+        );
+    }
+
+    #[test]
+    fn position_policies() {
+        let record = "1-3 a: abade".parse::<PasswordRecord>().unwrap();
+        assert!(AllPositions.is_valid(&record));
+        assert!(!ExactlyOnePosition { zero_based: false }.is_valid(&record));
+        // 0-based positions 1 and 3 are 'b' and 'd'
+        assert!(!ExactlyOnePosition { zero_based: true }.is_valid(&record));
+
+        let record = "0-1 a: abade".parse::<PasswordRecord>().unwrap();
+        assert!(ExactlyOnePosition { zero_based: true }.is_valid(&record));
+        assert!(!AllPositions.is_valid(&record));
+    }
+
+    #[test]
+    fn multibyte_passwords() {
+        let records = get_values("1-2 é: aéé\n2-3 ß: ßaßß\n1-3 ü: üaü\n1-1 😀: 😀x").unwrap();
+        assert_eq!(records[0].letter, "é");
+        assert_eq!(part1(&records), 4);
+        assert_eq!(part2(&records), 2);
+        assert!(AllPositions.is_valid(&records[2]));
+    }
+
+    #[test]
+    fn combining_marks_are_one_letter() {
+        // "e" followed by U+0301 COMBINING ACUTE ACCENT
+        let record = "1-2 e\u{301}: e\u{301}xe"
+            .parse::<PasswordRecord>()
+            .unwrap();
+        assert_eq!(record.letter, "e\u{301}");
+        assert!(CountInRange.is_valid(&record));
+        assert!(ExactlyOnePosition { zero_based: false }.is_valid(&record));
+
+        let err = "1-2 e\u{301}x: abc"
+            .parse::<PasswordRecord>()
+            .err()
+            .unwrap();
+        assert_eq!(err.kind, ParseRecordErrorKind::InvalidLetter);
+        let err = "1-2 é: ".parse::<PasswordRecord>().err().unwrap();
+        assert_eq!(
+            (err.kind, err.column),
+            (ParseRecordErrorKind::InvalidPassword, 8)
+        );
+    }
+
+    #[test]
+    fn out_of_range_positions_are_violations() {
+        let past_the_end = Some("position 20 is past the end of a 3-letter password".to_string());
+        let record = "3-20 a: xya".parse::<PasswordRecord>().unwrap();
+        assert_eq!(
+            ExactlyOnePosition { zero_based: false }.violation(&record),
+            past_the_end
+        );
+        assert_eq!(AllPositions.violation(&record), past_the_end);
+
+        let record = "5-20 a: xya".parse::<PasswordRecord>().unwrap();
+        assert_eq!(
+            ExactlyOnePosition { zero_based: false }.violation(&record),
+            Some("position 5 is past the end of a 3-letter password".to_string())
+        );
+        assert!(!ExactlyOnePosition { zero_based: true }.is_valid(&record));
+
+        let record = "0-2 x: xya".parse::<PasswordRecord>().unwrap();
+        assert_eq!(
+            AllPositions.violation(&record),
+            Some("position 0 is before the start of the password".to_string())
+        );
+        assert!(ExactlyOnePosition { zero_based: true }.is_valid(&record));
+    }
+
+    #[test]
+    fn validation_report() {
+        let records = get_values("1-3 a: abcde\n1-3 b: cdefg\n2-3 c: ccccc").unwrap();
+        let policies = policies_from_names("count-in-range,exactly-one-position").unwrap();
+        let reports = validate_records(&records, &policies);
+
+        assert!(reports[0].is_valid());
+        assert_eq!(
+            failing_reports(&records, &policies)
+                .iter()
+                .map(|report| report.line)
+                .collect::<Vec<usize>>(),
+            vec![2, 3]
+        );
+        assert_eq!(
+            reports[1].violations,
+            vec![
+                Violation {
+                    policy: "count-in-range".to_string(),
+                    reason: "letter 'b' appears 0 times, min 1".to_string(),
+                },
+                Violation {
+                    policy: "exactly-one-position".to_string(),
+                    reason: "letter 'b' is at neither position 1 nor 3".to_string(),
+                },
+            ]
+        );
+        assert_eq!(
+            reports[2].violations[0].reason,
+            "letter 'c' appears 5 times, max 3"
+        );
+
+        assert_eq!(
+            report_csv(&reports[..2]),
+            "line,minimum_count,maximum_count,letter,password,policy,reason\n\
+             1,1,3,a,abcde,,\n\
+             2,1,3,b,cdefg,count-in-range,\"letter 'b' appears 0 times, min 1\"\n\
+             2,1,3,b,cdefg,exactly-one-position,letter 'b' is at neither position 1 nor 3\n"
+        );
+        assert_eq!(
+            report_json(&reports[2..]),
+            "[{\"line\":3,\"minimum_count\":2,\"maximum_count\":3,\"letter\":\"c\",\"password\":\"ccccc\",\"valid\":false,\"violations\":[\
+             {\"policy\":\"count-in-range\",\"reason\":\"letter 'c' appears 5 times, max 3\"},\
+             {\"policy\":\"exactly-one-position\",\"reason\":\"letter 'c' is at both positions 2 and 3\"}]}]"
+        );
+    }
 }
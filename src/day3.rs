@@ -1,41 +1,376 @@
+use std::error::Error;
+use std::fmt;
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+
 use aoc_runner_derive::{aoc, aoc_generator};
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Slope {
+    pub right: usize,
+    pub down: usize,
+}
+
+impl Slope {
+    pub fn new(right: usize, down: usize) -> Self {
+        Slope { right, down }
+    }
+}
+
+/// The toboggan area. Each row is stored as a bitset with a set bit for every tree, and the
+/// pattern repeats to the right forever.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TreeMap {
+    width: usize,
+    height: usize,
+    words_per_row: usize,
+    trees: Vec<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseMapError {
+    Empty,
+    RaggedRow {
+        line: usize,
+        expected: usize,
+        found: usize,
+    },
+    InvalidSquare {
+        line: usize,
+        column: usize,
+        found: char,
+    },
+}
+
+impl fmt::Display for ParseMapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseMapError::Empty => write!(f, "map has no rows"),
+            ParseMapError::RaggedRow {
+                line,
+                expected,
+                found,
+            } => write!(
+                f,
+                "line {}: expected {} squares, found {}",
+                line, expected, found
+            ),
+            ParseMapError::InvalidSquare {
+                line,
+                column,
+                found,
+            } => write!(
+                f,
+                "line {}, column {}: expected '.' or '#', found {:?}",
+                line, column, found
+            ),
+        }
+    }
+}
+
+impl Error for ParseMapError {}
+
+impl FromStr for TreeMap {
+    type Err = ParseMapError;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let rows = input.lines().map(|l| l.trim_end()).collect::<Vec<&str>>();
+        let width = rows.first().map(|r| r.chars().count()).unwrap_or(0);
+        if width == 0 {
+            return Err(ParseMapError::Empty);
+        }
+
+        let words_per_row = width.div_ceil(64);
+        let mut trees = vec![0; words_per_row * rows.len()];
+        for (row_num, row) in rows.iter().enumerate() {
+            let found = row.chars().count();
+            if found != width {
+                return Err(ParseMapError::RaggedRow {
+                    line: row_num + 1,
+                    expected: width,
+                    found,
+                });
+            }
+            for (col, square) in row.chars().enumerate() {
+                match square {
+                    '.' => {}
+                    '#' => trees[row_num * words_per_row + col / 64] |= 1 << (col % 64),
+                    _ => {
+                        return Err(ParseMapError::InvalidSquare {
+                            line: row_num + 1,
+                            column: col + 1,
+                            found: square,
+                        })
+                    }
+                }
+            }
+        }
+
+        Ok(TreeMap {
+            width,
+            height: rows.len(),
+            words_per_row,
+            trees,
+        })
+    }
+}
+
+impl TreeMap {
+    /// The width of one repetition of the pattern.
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Whether there is a tree at `row`, `col`, where `col` may be beyond the pattern width.
+    pub fn is_tree(&self, row: usize, col: usize) -> bool {
+        let col = col % self.width;
+        self.trees[row * self.words_per_row + col / 64] & (1 << (col % 64)) != 0
+    }
+
+    /// The squares visited going from the top-left corner to the bottom row along `slope`, with
+    /// each column given within the first repetition of the pattern.
+    ///
+    /// Panics if `slope.down` is 0, since such a path never reaches the bottom.
+    pub fn path(&self, slope: Slope) -> impl Iterator<Item = (usize, usize)> {
+        assert!(slope.down > 0, "slope must move down");
+        let width = self.width;
+        // carrying the column modulo the width keeps any slope from overflowing
+        let right = slope.right % width;
+        (0..self.height)
+            .step_by(slope.down)
+            .scan(0, move |col, row| {
+                let square = (row, *col);
+                *col = (*col + right) % width;
+                Some(square)
+            })
+    }
+
+    pub fn count_trees(&self, slope: Slope) -> usize {
+        self.path(slope)
+            .filter(|(row, col)| self.is_tree(*row, *col))
+            .count()
+    }
+
+    /// Counts trees for every slope while walking the rows only once.
+    pub fn count_trees_for_slopes(&self, slopes: &[Slope]) -> Vec<usize> {
+        assert!(slopes.iter().all(|s| s.down > 0), "slope must move down");
+        let mut counts = vec![0; slopes.len()];
+        let mut cols = vec![0; slopes.len()];
+        for row in 0..self.height {
+            for ((slope, count), col) in slopes.iter().zip(&mut counts).zip(&mut cols) {
+                if row % slope.down != 0 {
+                    continue;
+                }
+                if self.is_tree(row, *col) {
+                    *count += 1;
+                }
+                *col = (*col + slope.right % self.width) % self.width;
+            }
+        }
+        counts
+    }
+
+    /// Draws the map with the squares visited along `slope` marked `O` for open ground and `X`
+    /// for a tree, repeating the pattern as many times as the path needs. The starting square is
+    /// marked too, since it is included in `count_trees`.
+    ///
+    /// Panics if the path is too wide for its columns to fit in a `usize`.
+    pub fn render_path(&self, slope: Slope) -> String {
+        let path = self
+            .path(slope)
+            .enumerate()
+            .map(|(step, (row, _))| {
+                let col = step.checked_mul(slope.right);
+                (row, col.expect("path is too wide to draw"))
+            })
+            .collect::<Vec<(usize, usize)>>();
+        let furthest_col = path.iter().map(|(_, col)| *col).max().unwrap_or(0);
+        let repetitions = furthest_col / self.width + 1;
+
+        let mut rows = (0..self.height)
+            .map(|row| {
+                (0..self.width * repetitions)
+                    .map(|col| if self.is_tree(row, col) { '#' } else { '.' })
+                    .collect::<Vec<char>>()
+            })
+            .collect::<Vec<Vec<char>>>();
+        for (row, col) in path {
+            rows[row][col] = if self.is_tree(row, col) { 'X' } else { 'O' };
+        }
+
+        rows.iter()
+            .map(|row| row.iter().collect::<String>())
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    /// Finds the slope within the given ranges that hits the fewest trees, preferring the
+    /// smallest `right` and then the smallest `down` on ties. A `down` of 0 is skipped.
+    pub fn least_trees_slope(
+        &self,
+        rights: RangeInclusive<usize>,
+        downs: RangeInclusive<usize>,
+    ) -> Option<(Slope, usize)> {
+        let slopes = rights
+            .flat_map(|right| {
+                downs
+                    .clone()
+                    .filter(|down| *down > 0)
+                    .map(move |down| Slope::new(right, down))
+            })
+            .collect::<Vec<Slope>>();
+        let counts = self.count_trees_for_slopes(&slopes);
+
+        slopes
+            .into_iter()
+            .zip(counts)
+            .min_by_key(|(_, count)| *count)
+    }
+}
+
 #[aoc_generator(day3)]
-pub fn get_values(input: &str) -> Vec<String> {
-    input.lines().map(|l| l.to_string()).collect()
+pub fn get_values(input: &str) -> Result<TreeMap, ParseMapError> {
+    input.parse()
 }
 
 #[aoc(day3, part1)]
-pub fn part1(inputs: &[String]) -> usize {
-    calculate_tree_count(&inputs, 1, 3)
+pub fn part1(map: &TreeMap) -> usize {
+    map.count_trees(Slope::new(3, 1))
 }
 
 #[aoc(day3, part2)]
-pub fn part2(inputs: &[String]) -> usize {
-    let down_one_right_one = calculate_tree_count(&inputs, 1, 1);
-    let down_one_right_three = calculate_tree_count(&inputs, 1, 3);
-    let down_one_right_five = calculate_tree_count(&inputs, 1, 5);
-    let down_one_right_seven = calculate_tree_count(&inputs, 1, 7);
-    let down_two_right_one = calculate_tree_count(&inputs, 2, 1);
-
-    down_one_right_one
-        * down_one_right_three
-        * down_one_right_five
-        * down_one_right_seven
-        * down_two_right_one
-}
-
-fn calculate_tree_count(rows: &[String], row_increment: usize, col_increment: usize) -> usize {
-    let mut tree_count = 0;
-    let mut x_pos = 0;
-    for row_num in (0..rows.len()).step_by(row_increment) {
-        let as_chars: Vec<char> = rows[row_num].chars().collect();
-        let current_char = as_chars[x_pos % rows[row_num].len()];
-        if current_char == '#' {
-            tree_count += 1;
-        }
-        x_pos += col_increment;
+pub fn part2(map: &TreeMap) -> usize {
+    map.count_trees_for_slopes(&[
+        Slope::new(1, 1),
+        Slope::new(3, 1),
+        Slope::new(5, 1),
+        Slope::new(7, 1),
+        Slope::new(1, 2),
+    ])
+    .iter()
+    .product()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = "..##.......
+#...#...#..
+.#....#..#.
+..#.#...#.#
+.#...##..#.
+..#.##.....
+.#.#.#....#
+.#........#
+#.##...#...
+#...##....#
+.#..#...#.#";
+
+    #[test]
+    fn example() {
+        let map = get_values(EXAMPLE).unwrap();
+        assert_eq!(map.width(), 11);
+        assert_eq!(map.height(), 11);
+        assert_eq!(part1(&map), 7);
+        assert_eq!(part2(&map), 336);
+        assert_eq!(map.count_trees(Slope::new(1, 2)), 2);
+    }
+
+    #[test]
+    fn wide_rows_repeat() {
+        let row = format!("{}#", ".".repeat(69));
+        let map = get_values(&format!("{}\n{}", row, row)).unwrap();
+        assert!(map.is_tree(0, 69));
+        assert!(map.is_tree(1, 139));
+        assert!(!map.is_tree(1, 70));
+        assert_eq!(map.count_trees(Slope::new(69, 1)), 1);
+    }
+
+    #[test]
+    fn steep_slopes_wrap() {
+        let map = get_values(EXAMPLE).unwrap();
+        let wrapped = Slope::new(usize::MAX % 11, 1);
+        assert_eq!(
+            map.count_trees(Slope::new(usize::MAX, 1)),
+            map.count_trees(wrapped)
+        );
+        assert_eq!(
+            map.count_trees_for_slopes(&[Slope::new(usize::MAX, 1), Slope::new(3 + 11 * 5, 2)]),
+            vec![map.count_trees(wrapped), map.count_trees(Slope::new(3, 2))]
+        );
+        assert_eq!(
+            map.path(Slope::new(14, 3)).collect::<Vec<(usize, usize)>>(),
+            vec![(0, 0), (3, 3), (6, 6), (9, 9)]
+        );
     }
 
-    tree_count
+    #[test]
+    fn least_trees_slope() {
+        let map = get_values(EXAMPLE).unwrap();
+        assert_eq!(
+            map.least_trees_slope(1..=7, 0..=2),
+            Some((Slope::new(5, 2), 0))
+        );
+        assert_eq!(map.least_trees_slope(1..=7, 0..=0), None);
+    }
+
+    #[test]
+    fn render_example_path() {
+        let map = get_values(EXAMPLE).unwrap();
+        let rendered = map.render_path(Slope::new(3, 1));
+        assert_eq!(
+            rendered,
+            "O.##.........##.........##.......
+#..O#...#..#...#...#..#...#...#..
+.#....X..#..#....#..#..#....#..#.
+..#.#...#O#..#.#...#.#..#.#...#.#
+.#...##..#..X...##..#..#...##..#.
+..#.##.......#.X#.......#.##.....
+.#.#.#....#.#.#.#.O..#.#.#.#....#
+.#........#.#........X.#........#
+#.##...#...#.##...#...#.X#...#...
+#...##....##...##....##...#X....#
+.#..#...#.#.#..#...#.#.#..#...X.#"
+        );
+        assert_eq!(
+            rendered.matches('X').count(),
+            map.count_trees(Slope::new(3, 1))
+        );
+    }
+
+    #[test]
+    fn render_steep_path() {
+        let map = get_values(EXAMPLE).unwrap();
+        let rendered = map.render_path(Slope::new(1, 2));
+        let rows = rendered.lines().collect::<Vec<&str>>();
+        assert_eq!(rows[0], "O.##.......");
+        assert_eq!(rows[1], "#...#...#..");
+        assert_eq!(rows[2], ".X....#..#.");
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!(get_values(""), Err(ParseMapError::Empty));
+        assert_eq!(
+            get_values("..#\n.#"),
+            Err(ParseMapError::RaggedRow {
+                line: 2,
+                expected: 3,
+                found: 2
+            })
+        );
+        assert_eq!(
+            get_values("..#\n.O."),
+            Err(ParseMapError::InvalidSquare {
+                line: 2,
+                column: 2,
+                found: 'O'
+            })
+        );
+    }
 }
@@ -1,173 +1,1267 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
 use aoc_runner_derive::{aoc, aoc_generator};
 use regex::Regex;
 
-#[derive(Debug)]
+use crate::records::split_records;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HeightUnit {
+    Cm,
+    In,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Height {
+    pub value: u32,
+    pub unit: HeightUnit,
+}
+
+const CM_PER_INCH: f64 = 2.54;
+
+impl Height {
+    pub fn as_cm(&self) -> f64 {
+        match self.unit {
+            HeightUnit::Cm => self.value as f64,
+            HeightUnit::In => self.value as f64 * CM_PER_INCH,
+        }
+    }
+
+    pub fn as_inches(&self) -> f64 {
+        self.as_cm() / CM_PER_INCH
+    }
+
+    /// The same height in `unit`, rounded to the nearest whole number.
+    pub fn to_unit(&self, unit: HeightUnit) -> Height {
+        let value = match unit {
+            HeightUnit::Cm => self.as_cm(),
+            HeightUnit::In => self.as_inches(),
+        };
+        Height {
+            value: value.round() as u32,
+            unit,
+        }
+    }
+}
+
+impl HeightUnit {
+    /// How the unit is written after a number.
+    pub fn suffix(&self) -> &'static str {
+        match self {
+            HeightUnit::Cm => "cm",
+            HeightUnit::In => "in",
+        }
+    }
+}
+
+impl fmt::Display for Height {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", self.value, self.unit.suffix())
+    }
+}
+
+/// A field value that can't be read as the type of its field.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseValueError {
+    pub expected: &'static str,
+    pub found: String,
+}
+
+impl ParseValueError {
+    fn new(expected: &'static str, found: &str) -> Self {
+        ParseValueError {
+            expected,
+            found: found.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for ParseValueError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "expected {}, found {:?}", self.expected, self.found)
+    }
+}
+
+impl Error for ParseValueError {}
+
+impl FromStr for Height {
+    type Err = ParseValueError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let error = || ParseValueError::new("a number followed by cm or in", value);
+        let (number, unit) = if let Some(number) = value.strip_suffix("cm") {
+            (number, HeightUnit::Cm)
+        } else if let Some(number) = value.strip_suffix("in") {
+            (number, HeightUnit::In)
+        } else {
+            return Err(error());
+        };
+        if number.is_empty() || !number.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(error());
+        }
+        Ok(Height {
+            value: number.parse().map_err(|_| error())?,
+            unit,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct HexColor {
+    pub red: u8,
+    pub green: u8,
+    pub blue: u8,
+}
+
+/// Parses `#` followed by exactly six lowercase hex digits, as the passport schema requires.
+impl FromStr for HexColor {
+    type Err = ParseValueError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let error = || ParseValueError::new("# and six lowercase hex digits", value);
+        let digits = value.strip_prefix('#').ok_or_else(error)?;
+        let is_digit = |b: u8| b.is_ascii_digit() || (b'a'..=b'f').contains(&b);
+        if digits.len() != 6 || !digits.bytes().all(is_digit) {
+            return Err(error());
+        }
+        let channel =
+            |idx: usize| u8::from_str_radix(&digits[idx..idx + 2], 16).map_err(|_| error());
+        Ok(HexColor {
+            red: channel(0)?,
+            green: channel(2)?,
+            blue: channel(4)?,
+        })
+    }
+}
+
+impl fmt::Display for HexColor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{:02x}{:02x}{:02x}", self.red, self.green, self.blue)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum EyeColor {
+    Amber,
+    Blue,
+    Brown,
+    Gray,
+    Green,
+    Hazel,
+    Other,
+}
+
+impl EyeColor {
+    pub fn code(&self) -> &'static str {
+        match self {
+            EyeColor::Amber => "amb",
+            EyeColor::Blue => "blu",
+            EyeColor::Brown => "brn",
+            EyeColor::Gray => "gry",
+            EyeColor::Green => "grn",
+            EyeColor::Hazel => "hzl",
+            EyeColor::Other => "oth",
+        }
+    }
+}
+
+impl FromStr for EyeColor {
+    type Err = ParseValueError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "amb" => Ok(EyeColor::Amber),
+            "blu" => Ok(EyeColor::Blue),
+            "brn" => Ok(EyeColor::Brown),
+            "gry" => Ok(EyeColor::Gray),
+            "grn" => Ok(EyeColor::Green),
+            "hzl" => Ok(EyeColor::Hazel),
+            "oth" => Ok(EyeColor::Other),
+            _ => Err(ParseValueError::new(
+                "one of amb, blu, brn, gry, grn, hzl or oth",
+                value,
+            )),
+        }
+    }
+}
+
+/// A single document. `fields` holds the key:value pairs in the order they were written, which
+/// is what a `Schema` checks; the typed fields are the standard passport keys parsed up front,
+/// and are `None` when the key is missing or its value can't be read as that type. The schema
+/// checks height, hair colour and eye colour through the typed fields.
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Passport {
-    birth_year: Option<String>,
-    issue_year: Option<String>,
-    expiration_year: Option<String>,
-    height: Option<String>,
-    hair_color: Option<String>,
-    eye_color: Option<String>,
-    passport_id: Option<String>,
-    country_id: Option<String>,
+    pub fields: Vec<(String, String)>,
+    pub birth_year: Option<u32>,
+    pub issue_year: Option<u32>,
+    pub expiration_year: Option<u32>,
+    pub height: Option<Height>,
+    pub hair_color: Option<HexColor>,
+    pub eye_color: Option<EyeColor>,
+    pub passport_id: Option<String>,
+    pub country_id: Option<String>,
+}
+
+fn parse_year(value: &str) -> Option<u32> {
+    if value.len() == 4 && value.bytes().all(|b| b.is_ascii_digit()) {
+        value.parse().ok()
+    } else {
+        None
+    }
 }
 
 impl Passport {
-    fn has_required_fields(&self) -> bool {
-        self.birth_year.is_some()
-            && self.issue_year.is_some()
-            && self.expiration_year.is_some()
-            && self.height.is_some()
-            && self.hair_color.is_some()
-            && self.eye_color.is_some()
-            && self.passport_id.is_some()
-    }
-
-    /*
-    byr (Birth Year) - four digits; at least 1920 and at most 2002.
-    iyr (Issue Year) - four digits; at least 2010 and at most 2020.
-    eyr (Expiration Year) - four digits; at least 2020 and at most 2030.
-    hgt (Height) - a number followed by either cm or in:
-    If cm, the number must be at least 150 and at most 193.
-    If in, the number must be at least 59 and at most 76.
-    hcl (Hair Color) - a # followed by exactly six characters 0-9 or a-f.
-    ecl (Eye Color) - exactly one of: amb blu brn gry grn hzl oth.
-    pid (Passport ID) - a nine-digit number, including leading zeroes.
-    */
-    fn is_valid(&self) -> bool {
-        lazy_static! {
-            static ref HAIR_COLOR_VALID: Regex = Regex::new(r"^#[0-9a-f]{6}$").unwrap();
-            static ref EYE_COLOR_VALID: Regex =
-                Regex::new(r"^(?:amb|blu|brn|gry|grn|hzl|oth)$").unwrap();
-            static ref PASSPORT_ID_VALID: Regex = Regex::new(r"^\d{9}$").unwrap();
-        }
-        let birth_year_valid = Passport::validate_year(&self.birth_year, 1920, 2002);
-        let issue_year_valid = Passport::validate_year(&self.issue_year, 2010, 2020);
-        let expiration_year_valid = Passport::validate_year(&self.expiration_year, 2020, 2030);
-        let height_valid = Passport::validate_height(&self.height, 150, 193, 59, 76);
-        let hair_color_valid = Passport::validate_with_regex(&self.hair_color, &HAIR_COLOR_VALID);
-        let eye_color_valid = Passport::validate_with_regex(&self.eye_color, &EYE_COLOR_VALID);
-        let passport_id_valid =
-            Passport::validate_with_regex(&self.passport_id, &PASSPORT_ID_VALID);
-
-        birth_year_valid
-            && issue_year_valid
-            && expiration_year_valid
-            && height_valid
-            && hair_color_valid
-            && eye_color_valid
-            && passport_id_valid
-    }
-
-    fn validate_year(val: &Option<String>, min_year: u32, max_year: u32) -> bool {
-        lazy_static! {
-            static ref YEAR_VALID: Regex = Regex::new(r"^\d{4}").unwrap();
+    pub fn from_fields(fields: Vec<(String, String)>) -> Self {
+        let get = |key: &str| {
+            fields
+                .iter()
+                .find(|(k, _)| k == key)
+                .map(|(_, v)| v.to_string())
+        };
+        Passport {
+            birth_year: get("byr").as_deref().and_then(parse_year),
+            issue_year: get("iyr").as_deref().and_then(parse_year),
+            expiration_year: get("eyr").as_deref().and_then(parse_year),
+            height: get("hgt").and_then(|v| v.parse().ok()),
+            hair_color: get("hcl").and_then(|v| v.parse().ok()),
+            eye_color: get("ecl").and_then(|v| v.parse().ok()),
+            passport_id: get("pid"),
+            country_id: get("cid"),
+            fields,
         }
-        if val.is_none() {
-            return false;
+    }
+
+    /// The value of the first field with this key.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.fields
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    pub fn validate(&self, schema: &Schema) -> Vec<Violation> {
+        schema.validate(self)
+    }
+}
+
+/// A single way in which a passport breaks its schema.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Violation {
+    MissingField {
+        key: String,
+    },
+    MalformedYear {
+        key: String,
+        value: String,
+    },
+    YearOutOfRange {
+        key: String,
+        year: u32,
+        min: u32,
+        max: u32,
+    },
+    BadUnit {
+        key: String,
+        value: String,
+    },
+    MeasureOutOfRange {
+        key: String,
+        value: u32,
+        unit: String,
+        min: u32,
+        max: u32,
+    },
+    PatternMismatch {
+        key: String,
+        value: String,
+    },
+    NotOneOf {
+        key: String,
+        value: String,
+    },
+    UnknownKey {
+        key: String,
+    },
+    DuplicateKey {
+        key: String,
+    },
+}
+
+impl Violation {
+    pub fn key(&self) -> &str {
+        match self {
+            Violation::MissingField { key }
+            | Violation::MalformedYear { key, .. }
+            | Violation::YearOutOfRange { key, .. }
+            | Violation::BadUnit { key, .. }
+            | Violation::MeasureOutOfRange { key, .. }
+            | Violation::PatternMismatch { key, .. }
+            | Violation::NotOneOf { key, .. }
+            | Violation::UnknownKey { key }
+            | Violation::DuplicateKey { key } => key,
         }
+    }
 
-        let value = val.as_ref().unwrap();
+    /// A short name for the kind of violation, used when summarising.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Violation::MissingField { .. } => "missing",
+            Violation::MalformedYear { .. } => "malformed year",
+            Violation::YearOutOfRange { .. } => "year out of range",
+            Violation::BadUnit { .. } => "bad unit",
+            Violation::MeasureOutOfRange { .. } => "out of range",
+            Violation::PatternMismatch { .. } => "malformed",
+            Violation::NotOneOf { .. } => "not allowed",
+            Violation::UnknownKey { .. } => "unknown key",
+            Violation::DuplicateKey { .. } => "duplicate key",
+        }
+    }
+}
 
-        if !YEAR_VALID.is_match(&value) {
-            return false;
-        };
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Violation::MissingField { key } => write!(f, "{} is missing", key),
+            Violation::MalformedYear { key, value } => {
+                write!(f, "{}: {:?} is not a four digit year", key, value)
+            }
+            Violation::YearOutOfRange {
+                key,
+                year,
+                min,
+                max,
+            } => write!(f, "{}: {} is not between {} and {}", key, year, min, max),
+            Violation::BadUnit { key, value } => {
+                write!(f, "{}: {:?} does not have a known unit", key, value)
+            }
+            Violation::MeasureOutOfRange {
+                key,
+                value,
+                unit,
+                min,
+                max,
+            } => write!(
+                f,
+                "{}: {}{} is not between {}{} and {}{}",
+                key, value, unit, min, unit, max, unit
+            ),
+            Violation::PatternMismatch { key, value } => {
+                write!(f, "{}: {:?} is malformed", key, value)
+            }
+            Violation::NotOneOf { key, value } => {
+                write!(f, "{}: {:?} is not an allowed value", key, value)
+            }
+            Violation::UnknownKey { key } => write!(f, "{} is not a known field", key),
+            Violation::DuplicateKey { key } => write!(f, "{} appears more than once", key),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnitRange {
+    pub unit: String,
+    pub min: u32,
+    pub max: u32,
+}
 
-        let parsed = value.parse::<u32>().unwrap();
-        min_year <= parsed && parsed <= max_year
+/// What a field's value has to look like.
+#[derive(Debug, Clone)]
+pub enum Rule {
+    Any,
+    Year { min: u32, max: u32 },
+    Measure(Vec<UnitRange>),
+    Pattern(Regex),
+    OneOf(Vec<String>),
+}
+
+impl Rule {
+    pub fn accepts(&self, value: &str) -> bool {
+        self.check("", value).is_none()
     }
 
-    fn validate_height(
-        val: &Option<String>,
-        min_cm: u32,
-        max_cm: u32,
-        min_in: u32,
-        max_in: u32,
-    ) -> bool {
+    /// Checks `value`, reporting any problem against the field `key`.
+    pub fn check(&self, key: &str, value: &str) -> Option<Violation> {
         lazy_static! {
-            static ref HEIGHT_VALID: Regex = Regex::new(r"^(\d+)(in|cm)").unwrap();
-        }
-        if val.is_none() {
-            return false;
-        }
-        match HEIGHT_VALID.captures(val.as_ref().unwrap()) {
-            Some(caps) => {
-                let number = caps
-                    .get(1)
-                    .map(|i| i.as_str().parse::<u32>().unwrap())
-                    .unwrap();
-                let unit = caps.get(2).map(|i| i.as_str()).unwrap();
-                if unit == "cm" {
-                    min_cm <= number && number <= max_cm
-                } else if unit == "in" {
-                    min_in <= number && number <= max_in
+            // `\d` would also match non-ASCII digits, which `u32::from_str` rejects
+            static ref MEASURE_VALID: Regex = Regex::new(r"^([0-9]+)([a-z]+)$").unwrap();
+        }
+        let key = key.to_string();
+        let value_string = || value.to_string();
+        match self {
+            Rule::Any => None,
+            Rule::Year { min, max } => {
+                let year = match parse_year(value) {
+                    Some(year) => year,
+                    None => {
+                        return Some(Violation::MalformedYear {
+                            key,
+                            value: value_string(),
+                        })
+                    }
+                };
+                if year < *min || *max < year {
+                    return Some(Violation::YearOutOfRange {
+                        key,
+                        year,
+                        min: *min,
+                        max: *max,
+                    });
+                }
+                None
+            }
+            Rule::Measure(units) => {
+                let measure = MEASURE_VALID.captures(value).map(|caps| {
+                    // an absurdly long number is out of range for any unit
+                    let number = caps[1].parse::<u32>().unwrap_or(u32::MAX);
+                    (number, caps.get(2).unwrap().as_str())
+                });
+                check_measure(units, key, value, measure)
+            }
+            Rule::Pattern(expr) => {
+                if expr.is_match(value) {
+                    None
                 } else {
-                    panic!("What happened?")
+                    Some(Violation::PatternMismatch {
+                        key,
+                        value: value_string(),
+                    })
+                }
+            }
+            Rule::OneOf(values) => {
+                if values.iter().any(|v| v == value) {
+                    None
+                } else {
+                    Some(Violation::NotOneOf {
+                        key,
+                        value: value_string(),
+                    })
+                }
+            }
+        }
+    }
+}
+
+/// Checks a number and unit, already read from `value`, against the unit ranges.
+fn check_measure(
+    units: &[UnitRange],
+    key: String,
+    value: &str,
+    measure: Option<(u32, &str)>,
+) -> Option<Violation> {
+    let found = measure.and_then(|(number, unit)| {
+        units
+            .iter()
+            .find(|range| range.unit == unit)
+            .map(|range| (number, range))
+    });
+    let (number, range) = match found {
+        Some(found) => found,
+        None => {
+            return Some(Violation::BadUnit {
+                key,
+                value: value.to_string(),
+            })
+        }
+    };
+    if number < range.min || range.max < number {
+        return Some(Violation::MeasureOutOfRange {
+            key,
+            value: number,
+            unit: range.unit.clone(),
+            min: range.min,
+            max: range.max,
+        });
+    }
+    None
+}
+
+#[derive(Debug, Clone)]
+pub struct FieldSpec {
+    pub key: String,
+    pub required: bool,
+    pub rule: Rule,
+}
+
+/// The fields a document may contain and the rule each one has to satisfy.
+#[derive(Debug, Clone)]
+pub struct Schema {
+    pub fields: Vec<FieldSpec>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SchemaError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for SchemaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "schema line {}: {}", self.line, self.message)
+    }
+}
+
+impl Error for SchemaError {}
+
+fn parse_rule(name: &str, args: &[&str]) -> Result<Rule, String> {
+    let number = |arg: &str| {
+        arg.parse::<u32>()
+            .map_err(|_| format!("expected a number, found {:?}", arg))
+    };
+    match (name, args) {
+        ("any", []) => Ok(Rule::Any),
+        ("year", [min, max]) => Ok(Rule::Year {
+            min: number(min)?,
+            max: number(max)?,
+        }),
+        ("measure", units) if !units.is_empty() && units.len() % 3 == 0 => units
+            .chunks(3)
+            .map(|unit| {
+                Ok(UnitRange {
+                    unit: unit[0].to_string(),
+                    min: number(unit[1])?,
+                    max: number(unit[2])?,
+                })
+            })
+            .collect::<Result<Vec<UnitRange>, String>>()
+            .map(Rule::Measure),
+        ("regex", [expr]) => Regex::new(&format!("^(?:{})$", expr))
+            .map(Rule::Pattern)
+            .map_err(|e| e.to_string()),
+        ("one-of", values) if !values.is_empty() => {
+            Ok(Rule::OneOf(values.iter().map(|v| v.to_string()).collect()))
+        }
+        ("any", _) | ("year", _) | ("measure", _) | ("regex", _) | ("one-of", _) => {
+            Err(format!("wrong arguments for rule {:?}", name))
+        }
+        _ => Err(format!("unknown rule {:?}", name)),
+    }
+}
+
+/// Parses the schema format described in `schemas/passport.schema`. Blank lines and lines
+/// starting with `#` are ignored.
+impl FromStr for Schema {
+    type Err = SchemaError;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let mut fields: Vec<FieldSpec> = vec![];
+        for (idx, line) in input.lines().enumerate() {
+            let error = |message: String| SchemaError {
+                line: idx + 1,
+                message,
+            };
+            let words = line.split_whitespace().collect::<Vec<&str>>();
+            if words.is_empty() || words[0].starts_with('#') {
+                continue;
+            }
+            if words.len() < 3 {
+                return Err(error(
+                    "expected a key, required or optional, and a rule".into(),
+                ));
+            }
+
+            let key = words[0].to_string();
+            if fields.iter().any(|f| f.key == key) {
+                return Err(error(format!("field {:?} is defined twice", key)));
+            }
+            let required = match words[1] {
+                "required" => true,
+                "optional" => false,
+                other => {
+                    return Err(error(format!(
+                        "expected required or optional, found {:?}",
+                        other
+                    )))
                 }
+            };
+            let rule = parse_rule(words[2], &words[3..]).map_err(error)?;
+
+            fields.push(FieldSpec {
+                key,
+                required,
+                rule,
+            });
+        }
+        Ok(Schema { fields })
+    }
+}
+
+impl Schema {
+    /// The rules from the puzzle, as written in `schemas/passport.schema`.
+    pub fn passport() -> &'static Schema {
+        lazy_static! {
+            static ref PASSPORT_SCHEMA: Schema =
+                include_str!("../schemas/passport.schema").parse().unwrap();
+        }
+        &PASSPORT_SCHEMA
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Schema, Box<dyn Error>> {
+        Ok(fs::read_to_string(path)?.parse()?)
+    }
+
+    pub fn field(&self, key: &str) -> Option<&FieldSpec> {
+        self.fields.iter().find(|f| f.key == key)
+    }
+
+    pub fn has_required_fields(&self, passport: &Passport) -> bool {
+        self.fields
+            .iter()
+            .filter(|spec| spec.required)
+            .all(|spec| passport.get(&spec.key).is_some())
+    }
+
+    /// Lists missing required fields first, in schema order, followed by problems with the
+    /// passport's own fields in the order they were written. Each repeat of a key is reported
+    /// once as a duplicate and not checked against the rule again.
+    pub fn validate(&self, passport: &Passport) -> Vec<Violation> {
+        let mut violations = self
+            .fields
+            .iter()
+            .filter(|spec| spec.required && passport.get(&spec.key).is_none())
+            .map(|spec| Violation::MissingField {
+                key: spec.key.clone(),
+            })
+            .collect::<Vec<Violation>>();
+
+        for (idx, (key, value)) in passport.fields.iter().enumerate() {
+            if passport.fields[..idx].iter().any(|(k, _)| k == key) {
+                violations.push(Violation::DuplicateKey { key: key.clone() });
+                continue;
+            }
+            match self.field(key) {
+                Some(spec) => violations.extend(check_field(&spec.rule, passport, key, value)),
+                None => violations.push(Violation::UnknownKey { key: key.clone() }),
             }
-            None => false,
         }
+
+        violations
+    }
+
+    /// A passport is valid when it has no violations at all, including unknown or repeated
+    /// keys.
+    pub fn is_valid(&self, passport: &Passport) -> bool {
+        self.validate(passport).is_empty()
     }
 
-    fn validate_with_regex(val: &Option<String>, expr: &Regex) -> bool {
-        match val {
-            Some(v) => expr.is_match(v),
-            None => false,
+    /// Counts violations by field and then by kind across a batch of passports.
+    pub fn summarize(
+        &self,
+        passports: &[Passport],
+    ) -> BTreeMap<String, BTreeMap<&'static str, usize>> {
+        let mut summary: BTreeMap<String, BTreeMap<&'static str, usize>> = BTreeMap::new();
+        for violation in passports.iter().flat_map(|p| self.validate(p)) {
+            *summary
+                .entry(violation.key().to_string())
+                .or_default()
+                .entry(violation.kind())
+                .or_insert(0) += 1;
         }
+        summary
+    }
+}
+
+/// Checks one of the passport's fields. Height, hair colour and eye colour are checked through
+/// the typed values `Passport::from_fields` read, so a value the type can't read is a violation
+/// even if its text would pass the rule.
+fn check_field(rule: &Rule, passport: &Passport, key: &str, value: &str) -> Option<Violation> {
+    let (key_string, value_string) = (key.to_string(), value.to_string());
+    // report what the rule makes of the text, if it objects to it
+    let unreadable = |violation| rule.check(key, value).or(Some(violation));
+    match (key, rule) {
+        ("hgt", Rule::Measure(units)) => match passport.height {
+            Some(height) => check_measure(
+                units,
+                key_string,
+                value,
+                Some((height.value, height.unit.suffix())),
+            ),
+            None => unreadable(Violation::BadUnit {
+                key: key_string,
+                value: value_string,
+            }),
+        },
+        ("hcl", Rule::Pattern(_)) => match passport.hair_color {
+            Some(color) => rule.check(key, &color.to_string()),
+            None => unreadable(Violation::PatternMismatch {
+                key: key_string,
+                value: value_string,
+            }),
+        },
+        ("ecl", Rule::OneOf(_)) => match passport.eye_color {
+            Some(color) => rule.check(key, color.code()),
+            None => unreadable(Violation::NotOneOf {
+                key: key_string,
+                value: value_string,
+            }),
+        },
+        _ => rule.check(key, value),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenErrorKind {
+    MissingColon,
+    EmptyKey,
+    EmptyValue,
+    ExtraColon,
+}
+
+/// A token in the batch that isn't a `key:value` pair. `line` and `column` are 1-based.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TokenError {
+    pub line: usize,
+    pub column: usize,
+    pub token: String,
+    pub kind: TokenErrorKind,
+}
+
+impl fmt::Display for TokenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let problem = match self.kind {
+            TokenErrorKind::MissingColon => "expected key:value",
+            TokenErrorKind::EmptyKey => "missing key",
+            TokenErrorKind::EmptyValue => "missing value",
+            TokenErrorKind::ExtraColon => "more than one ':'",
+        };
+        write!(
+            f,
+            "line {}, column {}: {} in {:?}",
+            self.line, self.column, problem, self.token
+        )
     }
 }
 
+impl Error for TokenError {}
+
+/// Splits a line on whitespace, keeping the 1-based column each token starts at.
+fn tokens(line: &str) -> Vec<(usize, &str)> {
+    let mut found = vec![];
+    let mut start: Option<usize> = None;
+    for (idx, c) in line.char_indices().chain(Some((line.len(), ' '))) {
+        match (start, c.is_whitespace()) {
+            (None, false) => start = Some(idx),
+            (Some(from), true) => {
+                found.push((line[..from].chars().count() + 1, &line[from..idx]));
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    found
+}
+
+fn parse_token(line: usize, column: usize, token: &str) -> Result<(String, String), TokenError> {
+    let error = |kind| TokenError {
+        line,
+        column,
+        token: token.to_string(),
+        kind,
+    };
+    let (key, value) = token
+        .split_once(':')
+        .ok_or_else(|| error(TokenErrorKind::MissingColon))?;
+    if key.is_empty() {
+        return Err(error(TokenErrorKind::EmptyKey));
+    }
+    if value.is_empty() {
+        return Err(error(TokenErrorKind::EmptyValue));
+    }
+    if value.contains(':') {
+        return Err(error(TokenErrorKind::ExtraColon));
+    }
+    Ok((key.to_string(), value.to_string()))
+}
+
+/// Reads passports separated by blank lines. Each passport is a series of whitespace separated
+/// `key:value` tokens; unknown and repeated keys are kept so that `Schema::validate` can report
+/// them.
 #[aoc_generator(day4)]
-pub fn get_values(input: &str) -> Vec<Passport> {
-    lazy_static! {
-        static ref SPLIT_EXPR: Regex = Regex::new(r"\n\n").unwrap();
-        static ref BIRTH_YEAR_EXPR: Regex = Regex::new(r"byr:(\S+)").unwrap();
-        static ref ISSUE_YEAR_EXPR: Regex = Regex::new(r"iyr:(\S+)").unwrap();
-        static ref EXPIRATION_YEAR_EXPR: Regex = Regex::new(r"eyr:(\S+)").unwrap();
-        static ref HEIGHT_EXPR: Regex = Regex::new(r"hgt:(\S+)").unwrap();
-        static ref HAIR_COLOR_EXPR: Regex = Regex::new(r"hcl:(\S+)").unwrap();
-        static ref EYE_COLOR_EXPR: Regex = Regex::new(r"ecl:(\S+)").unwrap();
-        static ref PASSPORT_ID_EXPR: Regex = Regex::new(r"pid:(\S+)").unwrap();
-        static ref COUNTRY_ID_EXPR: Regex = Regex::new(r"cid:(\S+)").unwrap();
-    }
-
-    SPLIT_EXPR
-        .split(input)
-        .map(|passport_raw| Passport {
-            birth_year: parse(&BIRTH_YEAR_EXPR, &passport_raw),
-            issue_year: parse(&ISSUE_YEAR_EXPR, &passport_raw),
-            expiration_year: parse(&EXPIRATION_YEAR_EXPR, &passport_raw),
-            height: parse(&HEIGHT_EXPR, &passport_raw),
-            hair_color: parse(&HAIR_COLOR_EXPR, &passport_raw),
-            eye_color: parse(&EYE_COLOR_EXPR, &passport_raw),
-            passport_id: parse(&PASSPORT_ID_EXPR, &passport_raw),
-            country_id: parse(&COUNTRY_ID_EXPR, &passport_raw),
+pub fn get_values(input: &str) -> Result<Vec<Passport>, TokenError> {
+    split_records(input)
+        .iter()
+        .map(|record| {
+            let mut fields = vec![];
+            for (line, text) in record.numbered_lines() {
+                for (column, token) in tokens(text) {
+                    fields.push(parse_token(line, column, token)?);
+                }
+            }
+            Ok(Passport::from_fields(fields))
         })
-        .collect::<Vec<Passport>>()
+        .collect()
 }
 
-fn parse(expr: &Regex, passport_raw: &str) -> Option<String> {
-    match expr.captures(passport_raw) {
-        Some(val) => val.get(1).map(|i| i.as_str().to_string()),
-        None => None,
+/// The mean height in centimetres of the passports that have a readable height.
+pub fn average_height_cm<'a, I>(passports: I) -> Option<f64>
+where
+    I: IntoIterator<Item = &'a Passport>,
+{
+    let heights = passports
+        .into_iter()
+        .filter_map(|p| p.height.map(|h| h.as_cm()))
+        .collect::<Vec<f64>>();
+    if heights.is_empty() {
+        None
+    } else {
+        Some(heights.iter().sum::<f64>() / heights.len() as f64)
     }
 }
 
+/// How many of the passports have each eye colour, skipping unreadable ones.
+pub fn eye_color_distribution<'a, I>(passports: I) -> BTreeMap<EyeColor, usize>
+where
+    I: IntoIterator<Item = &'a Passport>,
+{
+    let mut distribution = BTreeMap::new();
+    for color in passports.into_iter().filter_map(|p| p.eye_color) {
+        *distribution.entry(color).or_insert(0) += 1;
+    }
+    distribution
+}
+
 #[aoc(day4, part1)]
 pub fn part1(inputs: &[Passport]) -> usize {
     inputs
         .iter()
-        .filter(|passport| passport.has_required_fields())
-        .collect::<Vec<&Passport>>()
-        .len()
+        .filter(|passport| Schema::passport().has_required_fields(passport))
+        .count()
 }
 
 #[aoc(day4, part2)]
 pub fn part2(inputs: &[Passport]) -> usize {
     inputs
         .iter()
-        .filter(|passport| passport.is_valid())
-        .collect::<Vec<&Passport>>()
-        .len()
+        .filter(|passport| Schema::passport().is_valid(passport))
+        .count()
+}
+
+#[aoc(day4, part2, Violations)]
+pub fn part2_violations(inputs: &[Passport]) -> String {
+    Schema::passport()
+        .summarize(inputs)
+        .iter()
+        .map(|(key, kinds)| {
+            let counts = kinds
+                .iter()
+                .map(|(kind, count)| format!("{} {}", count, kind))
+                .collect::<Vec<String>>()
+                .join(", ");
+            format!("\n{}: {}", key, counts)
+        })
+        .collect()
+}
+
+#[aoc(day4, part2, Stats)]
+pub fn part2_stats(inputs: &[Passport]) -> String {
+    let valid = inputs
+        .iter()
+        .filter(|passport| Schema::passport().is_valid(passport))
+        .collect::<Vec<&Passport>>();
+    let average = match average_height_cm(valid.iter().cloned()) {
+        Some(cm) => format!("{:.1}cm", cm),
+        None => "n/a".to_string(),
+    };
+    let eye_colors = eye_color_distribution(valid.iter().cloned())
+        .iter()
+        .map(|(color, count)| format!("{} {}", color.code(), count))
+        .collect::<Vec<String>>()
+        .join(", ");
+    format!("\naverage height: {}\neye colours: {}", average, eye_colors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn example_part1() {
+        let passports = get_values(
+            "ecl:gry pid:860033327 eyr:2020 hcl:#fffffd
+byr:1937 iyr:2017 cid:147 hgt:183cm
+
+iyr:2013 ecl:amb cid:350 eyr:2023 pid:028048884
+hcl:#cfa07d byr:1929
+
+hcl:#ae17e1 iyr:2013
+eyr:2024
+ecl:brn pid:760753108 byr:1931
+hgt:179cm
+
+hcl:#cfa07d eyr:2025 pid:166559648
+iyr:2011 ecl:brn hgt:59in",
+        )
+        .unwrap();
+        assert_eq!(passports.len(), 4);
+        assert_eq!(passports[1].get("ecl"), Some("amb"));
+        assert_eq!(part1(&passports), 2);
+    }
+
+    #[test]
+    fn example_part2() {
+        let invalid = get_values(
+            "eyr:1972 cid:100
+hcl:#18171d ecl:amb hgt:170 pid:186cm iyr:2018 byr:1926
+
+iyr:2019
+hcl:#602927 eyr:1967 hgt:170cm
+ecl:grn pid:012533040 byr:1946
+
+hcl:dab227 iyr:2012
+ecl:brn hgt:182cm pid:021572410 eyr:2020 byr:1992 cid:277
+
+hgt:59cm ecl:zzz
+eyr:2038 hcl:74454a iyr:2023
+pid:3556412378 byr:2007",
+        )
+        .unwrap();
+        assert_eq!(part2(&invalid), 0);
+
+        let valid = get_values(
+            "pid:087499704 hgt:74in ecl:grn iyr:2012 eyr:2030 byr:1980
+hcl:#623a2f
+
+eyr:2029 ecl:blu cid:129 byr:1989
+iyr:2014 pid:896056539 hcl:#a97842 hgt:165cm
+
+hcl:#888785
+hgt:164cm byr:2001 iyr:2015 cid:88
+pid:545766238 ecl:hzl
+eyr:2022
+
+iyr:2010 hgt:158cm hcl:#b6652a ecl:blu byr:1944 eyr:2021 pid:093154719",
+        )
+        .unwrap();
+        assert_eq!(part2(&valid), 4);
+    }
+
+    #[test]
+    fn rules() {
+        let schema = Schema::passport();
+        let rule = |key| &schema.field(key).unwrap().rule;
+        assert!(rule("byr").accepts("2002"));
+        assert!(!rule("byr").accepts("2003"));
+        assert!(!rule("byr").accepts("20020"));
+        assert!(rule("hgt").accepts("60in"));
+        assert!(rule("hgt").accepts("190cm"));
+        assert!(!rule("hgt").accepts("190in"));
+        assert!(!rule("hgt").accepts("190"));
+        assert!(rule("hcl").accepts("#123abc"));
+        assert!(!rule("hcl").accepts("#123abz"));
+        assert!(!rule("hcl").accepts("123abc"));
+        assert!(rule("ecl").accepts("brn"));
+        assert!(!rule("ecl").accepts("wat"));
+        assert!(rule("pid").accepts("000000001"));
+        assert!(!rule("pid").accepts("0123456789"));
+    }
+
+    #[test]
+    fn typed_values_decide_validity() {
+        let schema = "hgt required measure cm 1 300 mm 1 3000
+hcl required regex #[0-9a-fA-F]{6}
+ecl required one-of amb xyz"
+            .parse::<Schema>()
+            .unwrap();
+        let passports =
+            get_values("hgt:1800mm hcl:#ABCDEF ecl:xyz\n\nhgt:99999999999cm hcl:#abcdef ecl:amb")
+                .unwrap();
+        assert_eq!(
+            schema.validate(&passports[0]),
+            vec![
+                Violation::BadUnit {
+                    key: "hgt".to_string(),
+                    value: "1800mm".to_string()
+                },
+                Violation::PatternMismatch {
+                    key: "hcl".to_string(),
+                    value: "#ABCDEF".to_string()
+                },
+                Violation::NotOneOf {
+                    key: "ecl".to_string(),
+                    value: "xyz".to_string()
+                },
+            ]
+        );
+        assert_eq!(
+            schema.validate(&passports[1]),
+            vec![Violation::MeasureOutOfRange {
+                key: "hgt".to_string(),
+                value: u32::MAX,
+                unit: "cm".to_string(),
+                min: 1,
+                max: 300
+            }]
+        );
+        // with a rule of another kind, only the text is checked
+        let any_height = "hgt required any".parse::<Schema>().unwrap();
+        assert!(any_height.is_valid(&get_values("hgt:tall").unwrap()[0]));
+    }
+
+    #[test]
+    fn only_ascii_digits_are_digits() {
+        let passports =
+            get_values("byr:١٩٩٠ iyr:2015 eyr:2025 hgt:١٧٠cm hcl:#123abc ecl:brn pid:١٢٣٤٥٦٧٨٩")
+                .unwrap();
+        assert_eq!(
+            passports[0].validate(Schema::passport()),
+            vec![
+                Violation::MalformedYear {
+                    key: "byr".to_string(),
+                    value: "١٩٩٠".to_string()
+                },
+                Violation::BadUnit {
+                    key: "hgt".to_string(),
+                    value: "١٧٠cm".to_string()
+                },
+                Violation::PatternMismatch {
+                    key: "pid".to_string(),
+                    value: "١٢٣٤٥٦٧٨٩".to_string()
+                },
+            ]
+        );
+        assert_eq!(part2(&passports), 0);
+    }
+
+    #[test]
+    fn custom_schema() {
+        let schema = "# library card
+name required regex [A-Z][a-z]+
+born optional year 1900 2020
+shelf required one-of a b c"
+            .parse::<Schema>()
+            .unwrap();
+        let cards =
+            get_values("name:Ada shelf:b\n\nname:ada shelf:b born:1815\n\nshelf:c").unwrap();
+        assert_eq!(
+            cards
+                .iter()
+                .map(|card| schema.is_valid(card))
+                .collect::<Vec<bool>>(),
+            vec![true, false, false]
+        );
+    }
+
+    #[test]
+    fn schema_errors() {
+        assert_eq!(
+            "byr required year 1920\n".parse::<Schema>().err(),
+            Some(SchemaError {
+                line: 1,
+                message: "wrong arguments for rule \"year\"".to_string()
+            })
+        );
+        assert_eq!(
+            "\nbyr sometimes any"
+                .parse::<Schema>()
+                .err()
+                .map(|e| e.line),
+            Some(2)
+        );
+        assert!("byr required shape round".parse::<Schema>().is_err());
+        assert!("byr required any\nbyr optional any"
+            .parse::<Schema>()
+            .is_err());
+    }
+
+    #[test]
+    fn violations() {
+        let passports = get_values(
+            "byr:1919 iyr:2010 eyr:2020 hgt:60 hcl:#12345g ecl:blu pid:000000001 pid:1
+
+byr:19190 iyr:2010 eyr:2020 hgt:200cm hcl:#123456 ecl:wat xyz:1",
+        )
+        .unwrap();
+
+        assert_eq!(
+            passports[0].validate(Schema::passport()),
+            vec![
+                Violation::YearOutOfRange {
+                    key: "byr".to_string(),
+                    year: 1919,
+                    min: 1920,
+                    max: 2002
+                },
+                Violation::BadUnit {
+                    key: "hgt".to_string(),
+                    value: "60".to_string()
+                },
+                Violation::PatternMismatch {
+                    key: "hcl".to_string(),
+                    value: "#12345g".to_string()
+                },
+                Violation::DuplicateKey {
+                    key: "pid".to_string()
+                },
+            ]
+        );
+        assert_eq!(
+            passports[1]
+                .validate(Schema::passport())
+                .iter()
+                .map(|v| v.to_string())
+                .collect::<Vec<String>>(),
+            vec![
+                "pid is missing",
+                "byr: \"19190\" is not a four digit year",
+                "hgt: 200cm is not between 150cm and 193cm",
+                "ecl: \"wat\" is not an allowed value",
+                "xyz is not a known field",
+            ]
+        );
+
+        let summary = Schema::passport().summarize(&passports);
+        assert_eq!(summary["byr"]["year out of range"], 1);
+        assert_eq!(summary["byr"]["malformed year"], 1);
+        assert_eq!(summary["pid"]["missing"], 1);
+        assert_eq!(summary["pid"]["duplicate key"], 1);
+        assert_eq!(summary.len(), 6);
+        assert_eq!(
+            part2_violations(&passports[1..]),
+            "\nbyr: 1 malformed year\necl: 1 not allowed\nhgt: 1 out of range\
+             \npid: 1 missing\nxyz: 1 unknown key"
+        );
+    }
+
+    #[test]
+    fn typed_fields() {
+        let passports = get_values(
+            "byr:1937 iyr:17 hgt:183cm\r\npid:0123 hcl:#fffffd  \r\n   \r\n\r\nhgt:59in byr:+937 cid:1\n\nhgt:190 \n",
+        )
+        .unwrap();
+        assert_eq!(passports.len(), 3);
+        assert_eq!(passports[0].birth_year, Some(1937));
+        assert_eq!(passports[0].issue_year, None);
+        assert_eq!(
+            passports[0].height,
+            Some(Height {
+                value: 183,
+                unit: HeightUnit::Cm
+            })
+        );
+        assert_eq!(
+            passports[0].hair_color,
+            Some(HexColor {
+                red: 0xff,
+                green: 0xff,
+                blue: 0xfd
+            })
+        );
+        assert_eq!(passports[0].passport_id.as_deref(), Some("0123"));
+        assert_eq!(passports[0].expiration_year, None);
+        assert_eq!(
+            passports[1].height,
+            Some(Height {
+                value: 59,
+                unit: HeightUnit::In
+            })
+        );
+        assert_eq!(passports[1].birth_year, None);
+        assert_eq!(passports[1].country_id.as_deref(), Some("1"));
+        assert_eq!(passports[2].height, None);
+        assert_eq!(
+            passports[2].fields,
+            vec![("hgt".to_string(), "190".to_string())]
+        );
+    }
+
+    #[test]
+    fn unanchored_keys_are_not_fields() {
+        let passports = get_values("xbyr:1990 byr:1990x").unwrap();
+        assert_eq!(passports[0].get("byr"), Some("1990x"));
+        assert_eq!(passports[0].birth_year, None);
+        assert_eq!(
+            passports[0].validate(Schema::passport())[..1],
+            [Violation::MissingField {
+                key: "iyr".to_string()
+            }]
+        );
+        assert!(passports[0]
+            .validate(Schema::passport())
+            .contains(&Violation::UnknownKey {
+                key: "xbyr".to_string()
+            }));
+    }
+
+    #[test]
+    fn token_errors() {
+        assert_eq!(
+            get_values("byr:1990\n\niyr:2010  eyr2020").err(),
+            Some(TokenError {
+                line: 3,
+                column: 11,
+                token: "eyr2020".to_string(),
+                kind: TokenErrorKind::MissingColon
+            })
+        );
+        let kind = |input| get_values(input).err().map(|e| e.kind);
+        assert_eq!(kind(":1990"), Some(TokenErrorKind::EmptyKey));
+        assert_eq!(kind("byr:"), Some(TokenErrorKind::EmptyValue));
+        assert_eq!(kind("byr:19:90"), Some(TokenErrorKind::ExtraColon));
+    }
+
+    #[test]
+    fn typed_values() {
+        let height = "74in".parse::<Height>().unwrap();
+        assert!((height.as_cm() - 187.96).abs() < 1e-9);
+        assert_eq!(height.to_unit(HeightUnit::Cm).to_string(), "188cm");
+        assert_eq!(
+            "150cm".parse::<Height>().unwrap().to_unit(HeightUnit::In),
+            Height {
+                value: 59,
+                unit: HeightUnit::In
+            }
+        );
+        assert!("cm".parse::<Height>().is_err());
+        assert_eq!(
+            "12ft".parse::<Height>(),
+            Err(ParseValueError {
+                expected: "a number followed by cm or in",
+                found: "12ft".to_string()
+            })
+        );
+
+        assert_eq!(
+            "#0a1b2c".parse::<HexColor>().unwrap(),
+            HexColor {
+                red: 10,
+                green: 27,
+                blue: 44
+            }
+        );
+        assert_eq!(
+            "#0a1B2c".parse::<HexColor>().unwrap_err().to_string(),
+            "expected # and six lowercase hex digits, found \"#0a1B2c\""
+        );
+        assert!("0a1b2c".parse::<HexColor>().is_err());
+        assert!("#0a1b2".parse::<HexColor>().is_err());
+        assert!("#+a1b2c".parse::<HexColor>().is_err());
+
+        let passport = Passport::from_fields(vec![("hcl".to_string(), "#ABCDEF".to_string())]);
+        assert_eq!(passport.hair_color, None);
+
+        assert_eq!("hzl".parse::<EyeColor>(), Ok(EyeColor::Hazel));
+        assert_eq!(EyeColor::Gray.code(), "gry");
+        assert!("zzz".parse::<EyeColor>().is_err());
+    }
+
+    #[test]
+    fn queries() {
+        let passports = get_values(
+            "hgt:74in ecl:grn\n\nhgt:165cm ecl:blu\n\nhgt:164cm ecl:grn\n\nhgt:tall ecl:zzz",
+        )
+        .unwrap();
+        let average = average_height_cm(&passports).unwrap();
+        assert!((average - (187.96 + 165.0 + 164.0) / 3.0).abs() < 1e-9);
+        assert_eq!(average_height_cm(&passports[3..]), None);
+
+        let distribution = eye_color_distribution(&passports);
+        assert_eq!(
+            distribution.into_iter().collect::<Vec<(EyeColor, usize)>>(),
+            vec![(EyeColor::Blue, 1), (EyeColor::Green, 2)]
+        );
+    }
 }
@@ -1,115 +1,363 @@
 use std::cmp;
+use std::error::Error;
+use std::fmt;
+use std::ops::RangeInclusive;
+use std::str::FromStr;
 
 use aoc_runner_derive::{aoc, aoc_generator};
 
-enum SearchStep {
-    Lower,
-    Upper,
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BoardingPassError {
+    InvalidGeometry { rows: u32, cols: u32 },
+    WrongLength { expected: usize, found: usize },
+    InvalidLetter { position: usize, found: char },
+    SeatOutOfRange { seat_id: u32, seat_count: u32 },
 }
 
-impl SearchStep {
-    fn from_char(input: &char) -> Self {
-        match input {
-            'F' | 'L' => SearchStep::Lower,
-            'B' | 'R' => SearchStep::Upper,
-            _ => panic!("Bad input char"),
+impl fmt::Display for BoardingPassError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BoardingPassError::InvalidGeometry { rows, cols } => write!(
+                f,
+                "{} rows by {} columns is not a plane of power of two dimensions",
+                rows, cols
+            ),
+            BoardingPassError::WrongLength { expected, found } => {
+                write!(f, "expected {} letters, found {}", expected, found)
+            }
+            BoardingPassError::InvalidLetter { position, found } => {
+                write!(f, "unexpected {:?} at position {}", found, position)
+            }
+            BoardingPassError::SeatOutOfRange {
+                seat_id,
+                seat_count,
+            } => write!(
+                f,
+                "seat {} is outside a plane of {} seats",
+                seat_id, seat_count
+            ),
         }
     }
 }
 
-pub struct Ticket {
-    row_search_steps: Vec<SearchStep>,
-    col_search_steps: Vec<SearchStep>,
+impl Error for BoardingPassError {}
+
+/// The shape of the plane. Both dimensions are powers of two, so that a boarding pass halves
+/// the remaining rows or columns with each letter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Geometry {
+    row_bits: u32,
+    col_bits: u32,
 }
 
-impl Ticket {
-    fn from_string(input: &str) -> Self {
-        Ticket {
-            row_search_steps: input[..7]
-                .chars()
-                .map(|l| SearchStep::from_char(&l))
-                .collect(),
-            col_search_steps: input[7..]
-                .chars()
-                .map(|l| SearchStep::from_char(&l))
-                .collect(),
+impl Geometry {
+    /// The 128 row, 8 column plane from the puzzle.
+    pub const STANDARD: Geometry = Geometry {
+        row_bits: 7,
+        col_bits: 3,
+    };
+
+    pub fn new(rows: u32, cols: u32) -> Result<Self, BoardingPassError> {
+        if !rows.is_power_of_two() || !cols.is_power_of_two() {
+            return Err(BoardingPassError::InvalidGeometry { rows, cols });
+        }
+        let row_bits = rows.trailing_zeros();
+        let col_bits = cols.trailing_zeros();
+        if row_bits + col_bits >= 32 {
+            return Err(BoardingPassError::InvalidGeometry { rows, cols });
         }
+        Ok(Geometry { row_bits, col_bits })
+    }
+
+    pub fn rows(&self) -> u32 {
+        1 << self.row_bits
+    }
+
+    pub fn cols(&self) -> u32 {
+        1 << self.col_bits
+    }
+
+    pub fn seat_count(&self) -> u32 {
+        self.rows() * self.cols()
     }
+
+    /// The number of letters in a boarding pass for this plane.
+    pub fn code_length(&self) -> usize {
+        (self.row_bits + self.col_bits) as usize
+    }
+}
+
+/// A seat on a plane. The boarding pass code is the seat id written in binary, with `B`/`R`
+/// for 1 and `F`/`L` for 0, where the first letters pick the row and the last pick the column.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BoardingPass {
+    pub row: u32,
+    pub col: u32,
+    pub geometry: Geometry,
 }
 
+impl BoardingPass {
+    pub fn decode(code: &str, geometry: Geometry) -> Result<Self, BoardingPassError> {
+        let found = code.chars().count();
+        if found != geometry.code_length() {
+            return Err(BoardingPassError::WrongLength {
+                expected: geometry.code_length(),
+                found,
+            });
+        }
+
+        let mut seat_id = 0;
+        for (idx, letter) in code.chars().enumerate() {
+            let is_row = idx < geometry.row_bits as usize;
+            let bit = match (letter, is_row) {
+                ('F', true) | ('L', false) => 0,
+                ('B', true) | ('R', false) => 1,
+                _ => {
+                    return Err(BoardingPassError::InvalidLetter {
+                        position: idx + 1,
+                        found: letter,
+                    })
+                }
+            };
+            seat_id = seat_id << 1 | bit;
+        }
+        BoardingPass::from_seat_id(seat_id, geometry)
+    }
+
+    pub fn from_seat_id(seat_id: u32, geometry: Geometry) -> Result<Self, BoardingPassError> {
+        if seat_id >= geometry.seat_count() {
+            return Err(BoardingPassError::SeatOutOfRange {
+                seat_id,
+                seat_count: geometry.seat_count(),
+            });
+        }
+        Ok(BoardingPass {
+            row: seat_id >> geometry.col_bits,
+            col: seat_id & (geometry.cols() - 1),
+            geometry,
+        })
+    }
+
+    pub fn seat_id(&self) -> u32 {
+        self.row * self.geometry.cols() + self.col
+    }
+
+    pub fn encode(&self) -> String {
+        let seat_id = self.seat_id();
+        let length = self.geometry.code_length();
+        (0..length)
+            .map(|idx| {
+                let is_set = seat_id >> (length - idx - 1) & 1 == 1;
+                match (idx < self.geometry.row_bits as usize, is_set) {
+                    (true, false) => 'F',
+                    (true, true) => 'B',
+                    (false, false) => 'L',
+                    (false, true) => 'R',
+                }
+            })
+            .collect()
+    }
+}
+
+/// Decodes a boarding pass for the standard plane.
+impl FromStr for BoardingPass {
+    type Err = BoardingPassError;
+
+    fn from_str(code: &str) -> Result<Self, Self::Err> {
+        BoardingPass::decode(code, Geometry::STANDARD)
+    }
+}
+
+/// A boarding pass in the batch that couldn't be read. `line` is 1-based.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParsePassError {
+    pub line: usize,
+    pub error: BoardingPassError,
+}
+
+impl fmt::Display for ParsePassError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.error)
+    }
+}
+
+impl Error for ParsePassError {}
+
 #[aoc_generator(day5)]
-pub fn get_values(input: &str) -> Vec<Ticket> {
-    input.lines().map(|l| Ticket::from_string(l)).collect()
+pub fn get_values(input: &str) -> Result<Vec<BoardingPass>, ParsePassError> {
+    input
+        .lines()
+        .enumerate()
+        .map(|(idx, l)| {
+            l.trim_end().parse().map_err(|error| ParsePassError {
+                line: idx + 1,
+                error,
+            })
+        })
+        .collect()
 }
 
 #[aoc(day5, part1)]
-pub fn part1(tickets: &[Ticket]) -> u32 {
+pub fn part1(passes: &[BoardingPass]) -> u32 {
     let mut highest_seen = 0;
-    for ticket in tickets {
-        let row = binary_search(127, &ticket.row_search_steps);
-        let col = binary_search(7, &ticket.col_search_steps);
-        let seat_id = row * 8 + col;
-
-        highest_seen = cmp::max(highest_seen, seat_id);
+    for pass in passes {
+        highest_seen = cmp::max(highest_seen, pass.seat_id());
     }
 
     highest_seen
 }
 
 #[aoc(day5, part2)]
-pub fn part2(tickets: &[Ticket]) -> u32 {
-    let mut seat_ids = vec![];
-    for ticket in tickets {
-        let row = binary_search(127, &ticket.row_search_steps);
-        let col = binary_search(7, &ticket.col_search_steps);
-        seat_ids.push(row * 8 + col);
+pub fn part2(passes: &[BoardingPass]) -> Result<u32, SeatSearchError> {
+    let map = SeatMap::new(Geometry::STANDARD, passes).map_err(SeatSearchError::Map)?;
+    let single_seat_gaps = map
+        .gaps()
+        .into_iter()
+        .filter(|gap| gap.start() == gap.end())
+        .map(|gap| *gap.start())
+        .collect::<Vec<u32>>();
+    match single_seat_gaps[..] {
+        [seat_id] => Ok(seat_id),
+        _ => Err(SeatSearchError::NotUnique(single_seat_gaps)),
     }
+}
 
-    seat_ids.sort();
-
-    let mut iter = seat_ids.iter().peekable();
-    loop {
-        let curr = iter.next();
-        let next = iter.peek();
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SeatSearchError {
+    Map(BoardingPassError),
+    /// Lists every single empty seat between two occupied ones, when there isn't exactly one.
+    NotUnique(Vec<u32>),
+}
 
-        if next.is_none() {
-            panic!("Failed to find!")
+impl fmt::Display for SeatSearchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SeatSearchError::Map(e) => e.fmt(f),
+            SeatSearchError::NotUnique(seats) => write!(
+                f,
+                "expected exactly one empty seat between two occupied seats, found {:?}",
+                seats
+            ),
         }
+    }
+}
 
-        let curr_value = *curr.unwrap();
-        let next_value = **next.unwrap();
+impl Error for SeatSearchError {}
 
-        if next_value == curr_value + 2 {
-            return curr_value + 1;
-        }
-    }
+/// How many boarding passes were issued for each seat of a plane.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SeatMap {
+    geometry: Geometry,
+    passes_per_seat: Vec<usize>,
 }
 
-fn binary_search(upper_bound: u32, steps: &[SearchStep]) -> u32 {
-    let mut current_lower_bound = 0;
-    let mut current_upper_bound = upper_bound;
-    for step in steps {
-        let current_range_size = current_upper_bound - current_lower_bound + 1;
-        if current_range_size % 2 != 0 {
-            panic!("Range size must be even!");
+impl SeatMap {
+    pub fn new(geometry: Geometry, passes: &[BoardingPass]) -> Result<Self, BoardingPassError> {
+        let mut passes_per_seat = vec![0; geometry.seat_count() as usize];
+        for pass in passes {
+            if pass.row >= geometry.rows() || pass.col >= geometry.cols() {
+                return Err(BoardingPassError::SeatOutOfRange {
+                    seat_id: pass.seat_id(),
+                    seat_count: geometry.seat_count(),
+                });
+            }
+            passes_per_seat[(pass.row * geometry.cols() + pass.col) as usize] += 1;
         }
-        let step_size = current_range_size / 2;
+        Ok(SeatMap {
+            geometry,
+            passes_per_seat,
+        })
+    }
 
-        match step {
-            SearchStep::Lower => {
-                current_upper_bound -= step_size;
-            }
-            SearchStep::Upper => {
-                current_lower_bound += step_size;
+    pub fn is_occupied(&self, seat_id: u32) -> bool {
+        self.passes_per_seat
+            .get(seat_id as usize)
+            .is_some_and(|count| *count > 0)
+    }
+
+    pub fn empty_seats(&self) -> Vec<u32> {
+        (0..self.geometry.seat_count())
+            .filter(|seat_id| !self.is_occupied(*seat_id))
+            .collect()
+    }
+
+    /// Every run of empty seats, including any at the front or back of the plane.
+    pub fn empty_runs(&self) -> Vec<RangeInclusive<u32>> {
+        let mut runs = vec![];
+        let mut run_start = None;
+        for seat_id in 0..self.geometry.seat_count() {
+            match (run_start, self.is_occupied(seat_id)) {
+                (None, false) => run_start = Some(seat_id),
+                (Some(start), true) => {
+                    runs.push(start..=seat_id - 1);
+                    run_start = None;
+                }
+                _ => {}
             }
         }
+        if let Some(start) = run_start {
+            runs.push(start..=self.geometry.seat_count() - 1);
+        }
+        runs
     }
 
-    if current_upper_bound != current_lower_bound {
-        panic!("Finished steps but didn't finish search!");
+    /// Runs of empty seats that have an occupied seat on both sides.
+    pub fn gaps(&self) -> Vec<RangeInclusive<u32>> {
+        let last = self.geometry.seat_count() - 1;
+        self.empty_runs()
+            .into_iter()
+            .filter(|run| *run.start() != 0 && *run.end() != last)
+            .collect()
     }
 
-    current_lower_bound
+    /// The empty seats before the first occupied one.
+    pub fn missing_at_front(&self) -> Option<RangeInclusive<u32>> {
+        self.empty_runs()
+            .into_iter()
+            .next()
+            .filter(|run| *run.start() == 0)
+    }
+
+    /// The empty seats after the last occupied one.
+    pub fn missing_at_back(&self) -> Option<RangeInclusive<u32>> {
+        let last = self.geometry.seat_count() - 1;
+        self.empty_runs()
+            .into_iter()
+            .last()
+            .filter(|run| *run.end() == last)
+    }
+
+    /// Seats with more than one boarding pass, and how many passes each has.
+    pub fn duplicates(&self) -> Vec<(u32, usize)> {
+        self.passes_per_seat
+            .iter()
+            .enumerate()
+            .filter(|(_, count)| **count > 1)
+            .map(|(seat_id, count)| (seat_id as u32, *count))
+            .collect()
+    }
+
+    /// One line per row, prefixed with the row number. Each seat is `.` when empty, `#` when
+    /// taken and `!` when more than one pass was issued for it.
+    pub fn render(&self) -> String {
+        let label_width = (self.geometry.rows() - 1).to_string().len();
+        self.passes_per_seat
+            .chunks(self.geometry.cols() as usize)
+            .enumerate()
+            .map(|(row, seats)| {
+                let seats = seats
+                    .iter()
+                    .map(|count| match count {
+                        0 => '.',
+                        1 => '#',
+                        _ => '!',
+                    })
+                    .collect::<String>();
+                format!("{:>width$} {}", row, seats, width = label_width)
+            })
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
 }
 
 #[cfg(test)]
@@ -117,32 +365,127 @@ mod tests {
     use super::*;
 
     #[test]
-    fn binary_search_small() {
+    fn decode_small() {
+        let geometry = Geometry::new(1, 8).unwrap();
+        assert_eq!(BoardingPass::decode("RLR", geometry).unwrap().col, 5);
+    }
+
+    #[test]
+    fn decode_large() {
+        let geometry = Geometry::new(128, 1).unwrap();
+        assert_eq!(BoardingPass::decode("FBFBBFF", geometry).unwrap().row, 44);
+    }
+
+    #[test]
+    fn examples() {
+        for (code, row, col, seat_id) in &[
+            ("FBFBBFFRLR", 44, 5, 357),
+            ("BFFFBBFRRR", 70, 7, 567),
+            ("FFFBBBFRRR", 14, 7, 119),
+            ("BBFFBBFRLL", 102, 4, 820),
+        ] {
+            let pass = code.parse::<BoardingPass>().unwrap();
+            assert_eq!((pass.row, pass.col, pass.seat_id()), (*row, *col, *seat_id));
+            assert_eq!(pass.encode(), *code);
+            assert_eq!(
+                BoardingPass::from_seat_id(*seat_id, Geometry::STANDARD).unwrap(),
+                pass
+            );
+        }
+    }
+
+    #[test]
+    fn other_geometry() {
+        let geometry = Geometry::new(4, 16).unwrap();
+        let pass = BoardingPass::decode("BFLRLR", geometry).unwrap();
+        assert_eq!((pass.row, pass.col, pass.seat_id()), (2, 5, 37));
         assert_eq!(
-            binary_search(
-                7,
-                &[SearchStep::Upper, SearchStep::Lower, SearchStep::Upper]
-            ),
-            5
+            BoardingPass::from_seat_id(63, geometry).unwrap().encode(),
+            "BBRRRR"
+        );
+        assert_eq!(
+            Geometry::new(6, 8),
+            Err(BoardingPassError::InvalidGeometry { rows: 6, cols: 8 })
         );
     }
 
     #[test]
-    fn binary_search_large() {
+    fn errors() {
         assert_eq!(
-            binary_search(
-                127,
-                &[
-                    SearchStep::Lower,
-                    SearchStep::Upper,
-                    SearchStep::Lower,
-                    SearchStep::Upper,
-                    SearchStep::Upper,
-                    SearchStep::Lower,
-                    SearchStep::Lower
-                ]
-            ),
-            44
+            "FBFBBFFRL".parse::<BoardingPass>(),
+            Err(BoardingPassError::WrongLength {
+                expected: 10,
+                found: 9
+            })
+        );
+        assert_eq!(
+            "FBFBBFRRLR".parse::<BoardingPass>(),
+            Err(BoardingPassError::InvalidLetter {
+                position: 7,
+                found: 'R'
+            })
+        );
+        assert_eq!(
+            "FBFBBFFRLé".parse::<BoardingPass>(),
+            Err(BoardingPassError::InvalidLetter {
+                position: 10,
+                found: 'é'
+            })
+        );
+        assert_eq!(
+            BoardingPass::from_seat_id(1024, Geometry::STANDARD),
+            Err(BoardingPassError::SeatOutOfRange {
+                seat_id: 1024,
+                seat_count: 1024
+            })
+        );
+        assert_eq!(
+            get_values("FBFBBFFRLR\nFBFBBFFXLR").err().map(|e| e.line),
+            Some(2)
+        );
+    }
+
+    fn passes(geometry: Geometry, seat_ids: &[u32]) -> Vec<BoardingPass> {
+        seat_ids
+            .iter()
+            .map(|id| BoardingPass::from_seat_id(*id, geometry).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn seat_map() {
+        let geometry = Geometry::new(4, 4).unwrap();
+        let map = SeatMap::new(geometry, &passes(geometry, &[3, 4, 6, 6, 9, 10, 13])).unwrap();
+
+        assert_eq!(map.empty_seats(), vec![0, 1, 2, 5, 7, 8, 11, 12, 14, 15]);
+        assert_eq!(map.gaps(), vec![5..=5, 7..=8, 11..=12]);
+        assert_eq!(map.missing_at_front(), Some(0..=2));
+        assert_eq!(map.missing_at_back(), Some(14..=15));
+        assert_eq!(map.duplicates(), vec![(6, 2)]);
+        assert_eq!(map.render(), "0 ...#\n1 #.!.\n2 .##.\n3 .#..");
+    }
+
+    #[test]
+    fn full_plane() {
+        let geometry = Geometry::new(2, 2).unwrap();
+        let map = SeatMap::new(geometry, &passes(geometry, &[0, 1, 2, 3])).unwrap();
+        assert!(map.empty_seats().is_empty());
+        assert!(map.gaps().is_empty());
+        assert_eq!(map.missing_at_front(), None);
+        assert_eq!(map.missing_at_back(), None);
+    }
+
+    #[test]
+    fn part2_needs_one_gap() {
+        let geometry = Geometry::STANDARD;
+        assert_eq!(part2(&passes(geometry, &[10, 11, 13, 14])), Ok(12));
+        assert_eq!(
+            part2(&passes(geometry, &[10, 12, 14])),
+            Err(SeatSearchError::NotUnique(vec![11, 13]))
+        );
+        assert_eq!(
+            part2(&passes(geometry, &[10, 11, 14])),
+            Err(SeatSearchError::NotUnique(vec![]))
         );
     }
 }
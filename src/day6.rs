@@ -1,49 +1,270 @@
-use std::collections::HashSet;
+use std::error::Error;
+use std::fmt;
+use std::ops::{BitAnd, BitOr};
+use std::str::FromStr;
 
 use aoc_runner_derive::{aoc, aoc_generator};
-use regex::Regex;
 
-#[aoc_generator(day6)]
-pub fn get_values(input: &str) -> Vec<String> {
-    lazy_static! {
-        static ref SPLIT_EXPR: Regex = Regex::new(r"\n\n").unwrap();
+use crate::records::split_records;
+
+const QUESTION_COUNT: usize = 26;
+
+/// A set of the questions `a` to `z`, stored as one bit per question.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Answers(u32);
+
+impl Answers {
+    pub const NONE: Answers = Answers(0);
+    pub const ALL: Answers = Answers((1 << QUESTION_COUNT) - 1);
+
+    pub fn contains(&self, question: char) -> bool {
+        question_index(question).is_some_and(|idx| self.0 & (1 << idx) != 0)
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.count_ones() as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    pub fn questions(&self) -> impl Iterator<Item = char> + '_ {
+        (0..QUESTION_COUNT)
+            .filter(move |idx| self.0 & (1 << idx) != 0)
+            .map(|idx| (b'a' + idx as u8) as char)
     }
-    SPLIT_EXPR.split(input).map(|l| l.to_string()).collect()
 }
 
-#[aoc(day6, part1)]
-pub fn part1(answer_groups: &[String]) -> usize {
-    let sets = answer_groups
-        .iter()
-        .map(|answer_group| {
-            answer_group
-                .chars()
-                .filter(|c| *c != '\n')
-                .collect::<HashSet<char>>()
-        })
-        .collect::<Vec<HashSet<char>>>();
+fn question_index(question: char) -> Option<usize> {
+    if question.is_ascii_lowercase() {
+        Some((question as u8 - b'a') as usize)
+    } else {
+        None
+    }
+}
+
+impl BitOr for Answers {
+    type Output = Answers;
+
+    fn bitor(self, other: Answers) -> Answers {
+        Answers(self.0 | other.0)
+    }
+}
 
-    let mut total = 0;
-    for answer_set in sets {
-        total += answer_set.len();
+impl BitAnd for Answers {
+    type Output = Answers;
+
+    fn bitand(self, other: Answers) -> Answers {
+        Answers(self.0 & other.0)
+    }
+}
+
+/// Reads one person's answers, which must be lowercase letters. The error gives the first
+/// character that isn't, on line 1.
+impl FromStr for Answers {
+    type Err = ParseAnswersError;
+
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        line.chars()
+            .enumerate()
+            .try_fold(Answers::NONE, |acc, (idx, c)| match question_index(c) {
+                Some(question) => Ok(Answers(acc.0 | 1 << question)),
+                None => Err(ParseAnswersError {
+                    line: 1,
+                    column: idx + 1,
+                    found: c,
+                }),
+            })
     }
+}
 
-    total
+impl fmt::Display for Answers {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.questions().collect::<String>())
+    }
 }
 
-#[aoc(day6, part2)]
-pub fn part2(answer_groups: &[String]) -> usize {
-    answer_groups
+/// The answers of each person in a group.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Group {
+    pub people: Vec<Answers>,
+}
+
+impl Group {
+    /// The questions anyone in the group answered.
+    pub fn union(&self) -> Answers {
+        self.people.iter().fold(Answers::NONE, |acc, a| acc | *a)
+    }
+
+    /// The questions everyone in the group answered.
+    pub fn intersection(&self) -> Answers {
+        self.people.iter().fold(Answers::ALL, |acc, a| acc & *a)
+    }
+
+    /// How many people answered each question, indexed from `a`.
+    pub fn question_counts(&self) -> [usize; QUESTION_COUNT] {
+        let mut counts = [0; QUESTION_COUNT];
+        for person in &self.people {
+            for (idx, count) in counts.iter_mut().enumerate() {
+                if person.0 & (1 << idx) != 0 {
+                    *count += 1;
+                }
+            }
+        }
+        counts
+    }
+
+    pub fn answered_by_at_least(&self, people: usize) -> Answers {
+        self.answered_by(|count| count >= people)
+    }
+
+    pub fn answered_by_exactly_one(&self) -> Answers {
+        self.answered_by(|count| count == 1)
+    }
+
+    fn answered_by<F: Fn(usize) -> bool>(&self, matches: F) -> Answers {
+        self.question_counts()
+            .iter()
+            .enumerate()
+            .filter(|(_, count)| matches(**count))
+            .fold(Answers::NONE, |acc, (idx, _)| Answers(acc.0 | 1 << idx))
+    }
+}
+
+/// How many people answered each question across all of the groups, indexed from `a`.
+pub fn question_histogram(groups: &[Group]) -> [usize; QUESTION_COUNT] {
+    let mut histogram = [0; QUESTION_COUNT];
+    for counts in groups.iter().map(|g| g.question_counts()) {
+        for (total, count) in histogram.iter_mut().zip(counts.iter()) {
+            *total += count;
+        }
+    }
+    histogram
+}
+
+/// A character that isn't a question letter. `line` and `column` are 1-based.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParseAnswersError {
+    pub line: usize,
+    pub column: usize,
+    pub found: char,
+}
+
+impl ParseAnswersError {
+    fn at_line(self, line: usize) -> Self {
+        ParseAnswersError { line, ..self }
+    }
+}
+
+impl fmt::Display for ParseAnswersError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "line {}, column {}: expected a question from a to z, found {:?}",
+            self.line, self.column, self.found
+        )
+    }
+}
+
+impl Error for ParseAnswersError {}
+
+#[aoc_generator(day6)]
+pub fn get_values(input: &str) -> Result<Vec<Group>, ParseAnswersError> {
+    split_records(input)
         .iter()
-        .map(|group| {
-            let mut lines = group.lines();
-            let accumulator: HashSet<char> =
-                lines.next().unwrap().chars().collect::<HashSet<char>>();
-            // this might be nicer as: map to HashSet, then fold_first, but fold_first is nightly only
-            lines.fold(accumulator, |acc, line| {
-                let current = line.chars().collect::<HashSet<char>>();
-                acc.intersection(&current).cloned().collect()
+        .map(|record| {
+            Ok(Group {
+                people: record
+                    .numbered_lines()
+                    .map(|(line, text)| text.parse::<Answers>().map_err(|e| e.at_line(line)))
+                    .collect::<Result<Vec<Answers>, ParseAnswersError>>()?,
             })
         })
-        .fold(0, |acc, set| acc + set.len())
+        .collect()
+}
+
+#[aoc(day6, part1)]
+pub fn part1(groups: &[Group]) -> usize {
+    groups.iter().map(|g| g.union().len()).sum()
+}
+
+#[aoc(day6, part2)]
+pub fn part2(groups: &[Group]) -> usize {
+    groups.iter().map(|g| g.intersection().len()).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = "abc
+
+a
+b
+c
+
+ab
+ac
+
+a
+a
+a
+a
+
+b";
+
+    #[test]
+    fn example() {
+        let groups = get_values(EXAMPLE).unwrap();
+        assert_eq!(groups.len(), 5);
+        assert_eq!(part1(&groups), 11);
+        assert_eq!(part2(&groups), 6);
+
+        let windows = EXAMPLE.replace('\n', "\r\n");
+        assert_eq!(get_values(&format!("\r\n{}\r\n \r\n", windows)), Ok(groups));
+    }
+
+    #[test]
+    fn group_queries() {
+        let group = Group {
+            people: vec![
+                "abcx".parse().unwrap(),
+                "abcy".parse().unwrap(),
+                "abcz".parse().unwrap(),
+                "az".parse().unwrap(),
+            ],
+        };
+        assert_eq!(group.union().to_string(), "abcxyz");
+        assert_eq!(group.intersection().to_string(), "a");
+        assert_eq!(group.answered_by_at_least(3).to_string(), "abc");
+        assert_eq!(group.answered_by_exactly_one().to_string(), "xy");
+        assert_eq!(group.question_counts()[25], 2);
+        assert!(group.union().contains('x'));
+        assert!(!group.union().contains('X'));
+    }
+
+    #[test]
+    fn histogram() {
+        let groups = get_values(EXAMPLE).unwrap();
+        let histogram = question_histogram(&groups);
+        assert_eq!(histogram[..3], [8, 4, 3]);
+        assert_eq!(histogram.iter().sum::<usize>(), 15);
+    }
+
+    #[test]
+    fn invalid_answers() {
+        assert_eq!(
+            get_values("ab\n\nc\naB").err(),
+            Some(ParseAnswersError {
+                line: 4,
+                column: 2,
+                found: 'B'
+            })
+        );
+        assert_eq!(
+            "ab1".parse::<Answers>().unwrap_err().to_string(),
+            "line 1, column 3: expected a question from a to z, found '1'"
+        );
+    }
 }
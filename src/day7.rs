@@ -1,98 +1,909 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
 
 use aoc_runner_derive::{aoc, aoc_generator};
-use regex::Regex;
 
-#[derive(Debug, Hash, PartialEq, Eq)]
-pub struct InnerBag {
-    color: String,
-    number: usize,
+/// One line of the puzzle input: a colour and the bags a bag of that colour must hold.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BagRule {
+    pub color: String,
+    pub contents: Vec<(usize, String)>,
 }
 
-#[aoc_generator(day7)]
-pub fn get_values(
-    input: &str,
-) -> (
-    HashMap<String, HashSet<String>>,
-    HashMap<String, HashSet<InnerBag>>,
-) {
-    lazy_static! {
-        static ref SPLIT_EXPR: Regex =
-            Regex::new(r"^(?P<color>[a-z ]+) bags contain (.+)\.").unwrap();
-        static ref INNER_BAGS_EXPR: Regex =
-            Regex::new(r"(?:, )?(?P<number>\d) (?P<color>[a-z ]+) bags?").unwrap();
-    }
-
-    input.lines().fold(
-        (HashMap::new(), HashMap::new()),
-        |(mut contained_by_map, mut contains_map), line| {
-            let parts = SPLIT_EXPR.captures(line).unwrap();
-            let outer_bag = parts.get(1).unwrap().as_str();
-            let inner_bags = parts.get(2).unwrap().as_str();
-
-            for caps in INNER_BAGS_EXPR.captures_iter(inner_bags) {
-                let current_contained_by = contained_by_map
-                    .entry(caps["color"].to_string())
-                    .or_insert(HashSet::new());
-                current_contained_by.insert(outer_bag.to_string());
-
-                let current_contains = contains_map
-                    .entry(outer_bag.to_string())
-                    .or_insert(HashSet::new());
-                current_contains.insert(InnerBag {
-                    color: caps["color"].to_string(),
-                    number: caps["number"].parse().unwrap(),
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseRuleErrorKind {
+    ExpectedColor,
+    ExpectedQuantity,
+    Expected(&'static str),
+    TrailingText,
+}
+
+/// Describes where a bag rule failed to parse. `column` is 1-based, and `found` is the word at
+/// that position, or empty at the end of the line. For trailing text, `found` is the rest of the
+/// line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseRuleError {
+    pub line: usize,
+    pub column: usize,
+    pub found: String,
+    pub kind: ParseRuleErrorKind,
+}
+
+impl ParseRuleError {
+    fn at_line(self, line: usize) -> Self {
+        ParseRuleError { line, ..self }
+    }
+}
+
+impl fmt::Display for ParseRuleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let expected = match self.kind {
+            ParseRuleErrorKind::ExpectedColor => "expected a colour".to_string(),
+            ParseRuleErrorKind::ExpectedQuantity => "expected a quantity of at least 1".to_string(),
+            ParseRuleErrorKind::Expected(text) => format!("expected {:?}", text),
+            ParseRuleErrorKind::TrailingText => "expected the end of the rule".to_string(),
+        };
+        write!(
+            f,
+            "line {}, column {}: {}, found {:?}",
+            self.line, self.column, expected, self.found
+        )
+    }
+}
+
+impl Error for ParseRuleError {}
+
+/// Reads a rule from left to right, keeping track of the offset for error reporting.
+struct RuleParser<'a> {
+    line: &'a str,
+    offset: usize,
+}
+
+impl<'a> RuleParser<'a> {
+    fn rest(&self) -> &'a str {
+        &self.line[self.offset..]
+    }
+
+    fn error(&self, kind: ParseRuleErrorKind) -> ParseRuleError {
+        let found = self.rest().split([' ', ',', '.']).next().unwrap_or("");
+        ParseRuleError {
+            line: 1,
+            column: self.line[..self.offset].chars().count() + 1,
+            found: found.to_string(),
+            kind,
+        }
+    }
+
+    fn eat(&mut self, text: &str) -> bool {
+        if self.rest().starts_with(text) {
+            self.offset += text.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, text: &'static str) -> Result<(), ParseRuleError> {
+        if self.eat(text) {
+            Ok(())
+        } else {
+            Err(self.error(ParseRuleErrorKind::Expected(text)))
+        }
+    }
+
+    /// The next word of lowercase letters, without consuming it.
+    fn peek_word(&self) -> &'a str {
+        let rest = self.rest();
+        let end = rest
+            .find(|c: char| !c.is_ascii_lowercase())
+            .unwrap_or(rest.len());
+        &rest[..end]
+    }
+
+    /// Reads the words of a colour up to, but not including, the following `bag` or `bags`.
+    fn color(&mut self) -> Result<String, ParseRuleError> {
+        let start = self.offset;
+        loop {
+            let word = self.peek_word();
+            if word.is_empty() || word == "bag" || word == "bags" {
+                break;
+            }
+            self.offset += word.len();
+            if !self.rest().starts_with(' ') {
+                return Err(self.error(ParseRuleErrorKind::Expected(" bags")));
+            }
+            self.offset += 1;
+        }
+        if self.offset == start {
+            return Err(self.error(ParseRuleErrorKind::ExpectedColor));
+        }
+        Ok(self.line[start..self.offset - 1].to_string())
+    }
+
+    fn quantity(&mut self) -> Result<usize, ParseRuleError> {
+        let rest = self.rest();
+        let digits = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        match rest[..digits].parse::<usize>() {
+            Ok(count) if count > 0 => {
+                self.offset += digits;
+                Ok(count)
+            }
+            _ => Err(self.error(ParseRuleErrorKind::ExpectedQuantity)),
+        }
+    }
+
+    fn rule(&mut self) -> Result<BagRule, ParseRuleError> {
+        let color = self.color()?;
+        self.expect("bags contain ")?;
+
+        let mut contents = vec![];
+        if !self.eat("no other bags") {
+            loop {
+                let count = self.quantity()?;
+                self.expect(" ")?;
+                let inner = self.color()?;
+                self.expect("bag")?;
+                self.eat("s");
+                contents.push((count, inner));
+                if !self.eat(", ") {
+                    break;
+                }
+            }
+        }
+
+        self.expect(".")?;
+        let rest = self.rest();
+        if !rest.is_empty() {
+            self.offset += rest.len() - rest.trim_start().len();
+            return Err(ParseRuleError {
+                found: self.rest().to_string(),
+                ..self.error(ParseRuleErrorKind::TrailingText)
+            });
+        }
+        Ok(BagRule { color, contents })
+    }
+}
+
+/// Parses a single `<colour> bags contain <contents>.` line, where the contents are either
+/// `no other bags` or a comma-separated list of `<quantity> <colour> bag(s)`. A trailing `\r`
+/// is ignored.
+impl FromStr for BagRule {
+    type Err = ParseRuleError;
+
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        RuleParser { line, offset: 0 }.rule()
+    }
+}
+
+/// Writes the rule in its canonical form, which parses back to the same rule.
+impl fmt::Display for BagRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} bags contain ", self.color)?;
+        if self.contents.is_empty() {
+            return write!(f, "no other bags.");
+        }
+        for (idx, (count, color)) in self.contents.iter().enumerate() {
+            let separator = if idx == 0 { "" } else { ", " };
+            let plural = if *count == 1 { "" } else { "s" };
+            write!(f, "{}{} {} bag{}", separator, count, color, plural)?;
+        }
+        write!(f, ".")
+    }
+}
+
+pub type ColorId = usize;
+
+/// Something wrong with a rule set as a whole. Lines are 1-based positions in the rule list.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RuleProblem {
+    /// The colours along a containment cycle, starting and ending with the same colour.
+    Cycle(Vec<String>),
+    Undefined {
+        color: String,
+    },
+    DuplicateRule {
+        color: String,
+        first_line: usize,
+        line: usize,
+    },
+    ContradictoryRule {
+        color: String,
+        first_line: usize,
+        line: usize,
+    },
+}
+
+impl fmt::Display for RuleProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RuleProblem::Cycle(path) => write!(f, "bags contain themselves: {}", path.join(" -> ")),
+            RuleProblem::Undefined { color } => {
+                write!(f, "{} bags are contained but have no rule", color)
+            }
+            RuleProblem::DuplicateRule {
+                color,
+                first_line,
+                line,
+            } => write!(
+                f,
+                "line {}: rule for {} bags repeats line {}",
+                line, color, first_line
+            ),
+            RuleProblem::ContradictoryRule {
+                color,
+                first_line,
+                line,
+            } => write!(
+                f,
+                "line {}: rule for {} bags contradicts line {}",
+                line, color, first_line
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidRules(pub Vec<RuleProblem>);
+
+impl fmt::Display for InvalidRules {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let problems = self
+            .0
+            .iter()
+            .map(|p| p.to_string())
+            .collect::<Vec<String>>();
+        write!(f, "invalid bag rules: {}", problems.join("; "))
+    }
+}
+
+impl Error for InvalidRules {}
+
+/// The containment rules as a graph over interned colour ids, with edges stored in both
+/// directions. A graph can only be built from a rule set that defines every colour exactly once
+/// and has no cycles.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BagGraph {
+    names: Vec<String>,
+    ids: HashMap<String, ColorId>,
+    contents: Vec<Vec<(ColorId, usize)>>,
+    containers: Vec<Vec<ColorId>>,
+}
+
+impl BagGraph {
+    pub fn from_rules(rules: &[BagRule]) -> Result<Self, InvalidRules> {
+        let mut graph = BagGraph::default();
+        let mut problems = vec![];
+        let mut defined_on: HashMap<ColorId, usize> = HashMap::new();
+
+        for (idx, rule) in rules.iter().enumerate() {
+            let outer = graph.intern(&rule.color);
+            if let Some(first_idx) = defined_on.get(&outer) {
+                let (first_line, line) = (first_idx + 1, idx + 1);
+                let color = rule.color.clone();
+                problems.push(if rules[*first_idx].contents == rule.contents {
+                    RuleProblem::DuplicateRule {
+                        color,
+                        first_line,
+                        line,
+                    }
+                } else {
+                    RuleProblem::ContradictoryRule {
+                        color,
+                        first_line,
+                        line,
+                    }
                 });
+                continue;
+            }
+            defined_on.insert(outer, idx);
+
+            for (count, color) in &rule.contents {
+                let inner = graph.intern(color);
+                graph.contents[outer].push((inner, *count));
+                graph.containers[inner].push(outer);
             }
+        }
+
+        problems.extend(
+            (0..graph.len())
+                .filter(|id| !defined_on.contains_key(id))
+                .map(|id| RuleProblem::Undefined {
+                    color: graph.names[id].clone(),
+                }),
+        );
+        problems.extend(graph.find_cycles().into_iter().map(|cycle| {
+            RuleProblem::Cycle(cycle.iter().map(|id| graph.names[*id].clone()).collect())
+        }));
+
+        if problems.is_empty() {
+            Ok(graph)
+        } else {
+            Err(InvalidRules(problems))
+        }
+    }
+
+    /// Finds a cycle through each containment edge that leads back into the current search
+    /// path, so every cycle is reported at least once.
+    fn find_cycles(&self) -> Vec<Vec<ColorId>> {
+        #[derive(Clone, Copy, PartialEq)]
+        enum State {
+            Unvisited,
+            OnPath,
+            Done,
+        }
 
-            (contained_by_map, contains_map)
-        },
-    )
+        let mut cycles = vec![];
+        let mut state = vec![State::Unvisited; self.len()];
+        for start in 0..self.len() {
+            if state[start] != State::Unvisited {
+                continue;
+            }
+            // each entry is a colour on the current path and the index of its next edge
+            let mut path: Vec<(ColorId, usize)> = vec![(start, 0)];
+            state[start] = State::OnPath;
+            while let Some((current, edge)) = path.last_mut() {
+                let current = *current;
+                match self.contents[current].get(*edge) {
+                    Some((inner, _)) => {
+                        *edge += 1;
+                        match state[*inner] {
+                            State::Unvisited => {
+                                state[*inner] = State::OnPath;
+                                path.push((*inner, 0));
+                            }
+                            State::OnPath => {
+                                let from = path.iter().position(|(id, _)| id == inner).unwrap();
+                                let mut cycle =
+                                    path[from..].iter().map(|(id, _)| *id).collect::<Vec<_>>();
+                                cycle.push(*inner);
+                                cycles.push(cycle);
+                            }
+                            State::Done => {}
+                        }
+                    }
+                    None => {
+                        state[current] = State::Done;
+                        path.pop();
+                    }
+                }
+            }
+        }
+        cycles
+    }
+
+    fn intern(&mut self, name: &str) -> ColorId {
+        if let Some(id) = self.ids.get(name) {
+            return *id;
+        }
+        let id = self.names.len();
+        self.names.push(name.to_string());
+        self.ids.insert(name.to_string(), id);
+        self.contents.push(vec![]);
+        self.containers.push(vec![]);
+        id
+    }
+
+    /// The number of distinct colours mentioned by the rules.
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    pub fn id(&self, name: &str) -> Option<ColorId> {
+        self.ids.get(name).cloned()
+    }
+
+    pub fn name(&self, id: ColorId) -> &str {
+        &self.names[id]
+    }
+
+    /// The bags directly inside a bag of this colour, with how many of each.
+    pub fn contents(&self, id: ColorId) -> &[(ColorId, usize)] {
+        &self.contents[id]
+    }
+
+    /// The colours whose bags directly hold a bag of this colour.
+    pub fn containers(&self, id: ColorId) -> &[ColorId] {
+        &self.containers[id]
+    }
+
+    /// Every colour that can eventually hold a bag of this colour.
+    pub fn ancestors(&self, id: ColorId) -> BTreeSet<ColorId> {
+        let mut found = BTreeSet::new();
+        let mut unexplored = self.containers[id].clone();
+        while let Some(current) = unexplored.pop() {
+            if found.insert(current) {
+                unexplored.extend(&self.containers[current]);
+            }
+        }
+        found
+    }
+
+    /// The colours nested inside a bag of this colour, ordered so that every colour comes
+    /// before the colours inside it.
+    fn nested_in_order(&self, id: ColorId) -> Vec<ColorId> {
+        let mut visited = vec![false; self.len()];
+        let mut finished = vec![];
+        let mut path = vec![(id, 0)];
+        visited[id] = true;
+        while let Some((current, edge)) = path.last_mut() {
+            match self.contents[*current].get(*edge) {
+                Some((inner, _)) => {
+                    *edge += 1;
+                    if !visited[*inner] {
+                        visited[*inner] = true;
+                        path.push((*inner, 0));
+                    }
+                }
+                None => {
+                    finished.push(*current);
+                    path.pop();
+                }
+            }
+        }
+        finished.reverse();
+        finished
+    }
+
+    /// Every colour nested somewhere inside a bag of this colour, with the total number of
+    /// bags of that colour inside it, or `None` if a total doesn't fit in a `usize`.
+    pub fn descendants(&self, id: ColorId) -> Option<BTreeMap<ColorId, usize>> {
+        let mut bags = vec![0usize; self.len()];
+        bags[id] = 1;
+        for current in self.nested_in_order(id) {
+            for (inner, count) in &self.contents[current] {
+                bags[*inner] = bags[current]
+                    .checked_mul(*count)
+                    .and_then(|n| n.checked_add(bags[*inner]))?;
+            }
+        }
+        bags[id] = 0;
+        Some(
+            bags.into_iter()
+                .enumerate()
+                .filter(|(_, count)| *count > 0)
+                .collect(),
+        )
+    }
+
+    /// How many bags a bag of this colour holds in total, or `None` if that doesn't fit in a
+    /// `usize`.
+    pub fn total_nested(&self, id: ColorId) -> Option<usize> {
+        // working from the innermost colours out, every colour's contents are already counted
+        let mut totals = vec![0usize; self.len()];
+        for current in self.nested_in_order(id).into_iter().rev() {
+            for (inner, count) in &self.contents[current] {
+                totals[current] = totals[*inner]
+                    .checked_add(1)
+                    .and_then(|n| n.checked_mul(*count))
+                    .and_then(|n| n.checked_add(totals[current]))?;
+            }
+        }
+        Some(totals[id])
+    }
+
+    /// The colours whose bags hold no other bags.
+    pub fn leaves(&self) -> Vec<ColorId> {
+        (0..self.len())
+            .filter(|id| self.contents[*id].is_empty())
+            .collect()
+    }
+
+    /// Writes the graph as a Graphviz DOT document, with an edge from each bag to each colour
+    /// it holds, labelled with the quantity.
+    pub fn to_dot(&self, scope: DotScope) -> String {
+        let included = match scope {
+            DotScope::All => (0..self.len()).collect(),
+            DotScope::AncestorsOf(id) => {
+                let mut ancestors = self.ancestors(id);
+                ancestors.insert(id);
+                ancestors
+            }
+            DotScope::DescendantsOf(id) => self.nested_in_order(id).into_iter().collect(),
+        };
+
+        let mut dot = String::from("digraph bags {\n");
+        for id in &included {
+            dot += &format!("    {};\n", dot_id(&self.names[*id]));
+        }
+        for outer in &included {
+            for (inner, count) in &self.contents[*outer] {
+                if included.contains(inner) {
+                    dot += &format!(
+                        "    {} -> {} [label=\"{}\"];\n",
+                        dot_id(&self.names[*outer]),
+                        dot_id(&self.names[*inner]),
+                        count
+                    );
+                }
+            }
+        }
+        dot += "}\n";
+        dot
+    }
 }
 
-#[aoc(day7, part1)]
-pub fn part1(
-    (inputs, _): &(
-        HashMap<String, HashSet<String>>,
-        HashMap<String, HashSet<InnerBag>>,
-    ),
-) -> usize {
-    let mut unexplored_containers = inputs
-        .get(&"shiny gold".to_string())
-        .unwrap()
-        .into_iter()
-        .collect::<Vec<&String>>();
-    let mut all_containers: HashSet<String> = HashSet::new();
+/// Which colours to include when exporting a graph. The restricted scopes include the chosen
+/// colour itself.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DotScope {
+    All,
+    AncestorsOf(ColorId),
+    DescendantsOf(ColorId),
+}
+
+fn dot_id(name: &str) -> String {
+    format!("\"{}\"", name.replace('\\', "\\\\").replace('"', "\\\""))
+}
 
-    while unexplored_containers.len() > 0 {
-        let current_container = unexplored_containers.pop().unwrap();
-        all_containers.insert(current_container.to_string());
-        match inputs.get(current_container) {
-            Some(v) => v.iter().for_each(|val| unexplored_containers.push(val)),
-            None => {}
+#[aoc_generator(day7)]
+pub fn get_values(input: &str) -> Result<BagGraph, RulesError> {
+    let rules = input
+        .lines()
+        .enumerate()
+        .map(|(idx, line)| line.parse::<BagRule>().map_err(|e| e.at_line(idx + 1)))
+        .collect::<Result<Vec<BagRule>, ParseRuleError>>()
+        .map_err(RulesError::Parse)?;
+
+    BagGraph::from_rules(&rules).map_err(RulesError::Invalid)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RulesError {
+    Parse(ParseRuleError),
+    Invalid(InvalidRules),
+}
+
+impl fmt::Display for RulesError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RulesError::Parse(e) => e.fmt(f),
+            RulesError::Invalid(e) => e.fmt(f),
         }
     }
+}
+
+impl Error for RulesError {}
 
-    all_containers.len()
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BagQueryError {
+    /// No rule mentions the colour.
+    UnknownColor(String),
+    /// The number of bags inside the colour doesn't fit in a `usize`.
+    TotalOverflow(String),
+}
+
+impl fmt::Display for BagQueryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BagQueryError::UnknownColor(name) => write!(f, "no rule mentions {} bags", name),
+            BagQueryError::TotalOverflow(name) => {
+                write!(f, "too many bags inside a {} bag to count", name)
+            }
+        }
+    }
+}
+
+impl Error for BagQueryError {}
+
+const TARGET_COLOR: &str = "shiny gold";
+
+fn target_id(graph: &BagGraph) -> Result<ColorId, BagQueryError> {
+    graph
+        .id(TARGET_COLOR)
+        .ok_or_else(|| BagQueryError::UnknownColor(TARGET_COLOR.to_string()))
+}
+
+#[aoc(day7, part1)]
+pub fn part1(graph: &BagGraph) -> Result<usize, BagQueryError> {
+    Ok(graph.ancestors(target_id(graph)?).len())
 }
 
 #[aoc(day7, part2)]
-pub fn part2(
-    (_, inputs): &(
-        HashMap<String, HashSet<String>>,
-        HashMap<String, HashSet<InnerBag>>,
-    ),
-) -> usize {
-    get_bag_total(&inputs, "shiny gold")
+pub fn part2(graph: &BagGraph) -> Result<usize, BagQueryError> {
+    graph
+        .total_nested(target_id(graph)?)
+        .ok_or_else(|| BagQueryError::TotalOverflow(TARGET_COLOR.to_string()))
 }
 
-fn get_bag_total(map: &HashMap<String, HashSet<InnerBag>>, color: &str) -> usize {
-    let entry = map.get(color);
-    if entry.is_none() {
-        return 0;
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = "light red bags contain 1 bright white bag, 2 muted yellow bags.
+dark orange bags contain 3 bright white bags, 4 muted yellow bags.
+bright white bags contain 1 shiny gold bag.
+muted yellow bags contain 2 shiny gold bags, 9 faded blue bags.
+shiny gold bags contain 1 dark olive bag, 2 vibrant plum bags.
+dark olive bags contain 3 faded blue bags, 4 dotted black bags.
+vibrant plum bags contain 5 faded blue bags, 6 dotted black bags.
+faded blue bags contain no other bags.
+dotted black bags contain no other bags.";
+
+    fn names<'a, I: IntoIterator<Item = &'a ColorId>>(graph: &BagGraph, ids: I) -> Vec<&str> {
+        ids.into_iter().map(|id| graph.name(*id)).collect()
+    }
+
+    #[test]
+    fn example() {
+        let graph = get_values(EXAMPLE).unwrap();
+        assert_eq!(graph.len(), 9);
+        assert_eq!(part1(&graph), Ok(4));
+        assert_eq!(part2(&graph), Ok(32));
+    }
+
+    #[test]
+    fn missing_shiny_gold_is_an_error() {
+        let graph = get_values(
+            "faded blue bags contain 2 dotted black bags.
+dotted black bags contain no other bags.",
+        )
+        .unwrap();
+        let unknown = BagQueryError::UnknownColor("shiny gold".to_string());
+        assert_eq!(part1(&graph), Err(unknown.clone()));
+        assert_eq!(part2(&graph), Err(unknown));
+        assert_eq!(
+            part1(&graph).unwrap_err().to_string(),
+            "no rule mentions shiny gold bags"
+        );
+    }
+
+    #[test]
+    fn queries_for_any_color() {
+        let graph = get_values(EXAMPLE).unwrap();
+        let id = |name| graph.id(name).unwrap();
+
+        assert_eq!(
+            names(&graph, &graph.ancestors(id("faded blue"))),
+            vec![
+                "light red",
+                "bright white",
+                "muted yellow",
+                "dark orange",
+                "shiny gold",
+                "dark olive",
+                "vibrant plum"
+            ]
+        );
+        assert!(graph.ancestors(id("light red")).is_empty());
+
+        let descendants = graph.descendants(id("shiny gold")).unwrap();
+        assert_eq!(descendants[&id("dark olive")], 1);
+        assert_eq!(descendants[&id("vibrant plum")], 2);
+        assert_eq!(descendants[&id("faded blue")], 13);
+        assert_eq!(descendants[&id("dotted black")], 16);
+        assert_eq!(graph.total_nested(id("dark olive")), Some(7));
+        assert_eq!(graph.total_nested(id("faded blue")), Some(0));
+
+        assert_eq!(
+            names(&graph, &graph.leaves()),
+            vec!["faded blue", "dotted black"]
+        );
+        assert_eq!(graph.id("mauve"), None);
+    }
+
+    fn rule(color: &str, contents: &[(usize, &str)]) -> BagRule {
+        BagRule {
+            color: color.to_string(),
+            contents: contents
+                .iter()
+                .map(|(count, color)| (*count, color.to_string()))
+                .collect(),
+        }
     }
 
-    entry.unwrap().iter().fold(0, |acc, i| {
-        acc + i.number + i.number * get_bag_total(map, &i.color)
-    })
+    #[test]
+    fn cycles_are_reported() {
+        let rules = [
+            rule("red", &[(1, "blue")]),
+            rule("blue", &[(2, "green"), (1, "gold")]),
+            rule("green", &[(1, "red")]),
+            rule("gold", &[(1, "gold")]),
+        ];
+        assert_eq!(
+            BagGraph::from_rules(&rules),
+            Err(InvalidRules(vec![
+                RuleProblem::Cycle(vec![
+                    "red".to_string(),
+                    "blue".to_string(),
+                    "green".to_string(),
+                    "red".to_string()
+                ]),
+                RuleProblem::Cycle(vec!["gold".to_string(), "gold".to_string()]),
+            ]))
+        );
+    }
+
+    #[test]
+    fn definitions_are_checked() {
+        let rules = [
+            rule("red", &[(1, "blue")]),
+            rule("green", &[]),
+            rule("red", &[(1, "blue")]),
+            rule("green", &[(3, "red")]),
+        ];
+        let problems = BagGraph::from_rules(&rules).err().unwrap().0;
+        assert_eq!(
+            problems,
+            vec![
+                RuleProblem::DuplicateRule {
+                    color: "red".to_string(),
+                    first_line: 1,
+                    line: 3
+                },
+                RuleProblem::ContradictoryRule {
+                    color: "green".to_string(),
+                    first_line: 2,
+                    line: 4
+                },
+                RuleProblem::Undefined {
+                    color: "blue".to_string()
+                },
+            ]
+        );
+        assert_eq!(
+            problems[1].to_string(),
+            "line 4: rule for green bags contradicts line 2"
+        );
+    }
+
+    #[test]
+    fn long_chains_do_not_overflow_the_stack() {
+        let rules = (0..100_000)
+            .map(|level| BagRule {
+                color: format!("level {}", level),
+                contents: vec![(1, format!("level {}", level + 1))],
+            })
+            .chain(Some(rule("level 100000", &[])))
+            .collect::<Vec<BagRule>>();
+        let graph = BagGraph::from_rules(&rules).unwrap();
+        assert_eq!(graph.total_nested(0), Some(100_000));
+        assert_eq!(graph.descendants(0).map(|d| d.len()), Some(100_000));
+    }
+
+    #[test]
+    fn deep_nesting_is_memoized_and_checked() {
+        // each level holds 2 bags of each of the next two levels, which would take 2^64 steps
+        // to walk without memoization and overflows a u64 count
+        let mut rules = (0..64)
+            .map(|level| {
+                let next = format!("level {}", level + 1);
+                let after = format!("level {}", level + 2);
+                BagRule {
+                    color: format!("level {}", level),
+                    contents: vec![(2, next), (2, after)],
+                }
+            })
+            .collect::<Vec<BagRule>>();
+        rules.push(rule("level 64", &[(1, "level 65")]));
+        rules.push(rule("level 65", &[]));
+        let graph = BagGraph::from_rules(&rules).unwrap();
+
+        assert_eq!(graph.total_nested(graph.id("level 63").unwrap()), Some(6));
+        assert_eq!(graph.total_nested(graph.id("level 60").unwrap()), Some(144));
+        assert_eq!(graph.total_nested(graph.id("level 0").unwrap()), None);
+        assert_eq!(graph.descendants(graph.id("level 0").unwrap()), None);
+        assert_eq!(
+            graph.descendants(graph.id("level 62").unwrap()).unwrap()[&65],
+            10
+        );
+    }
+
+    #[test]
+    fn rules_round_trip() {
+        for line in EXAMPLE.lines() {
+            assert_eq!(line.parse::<BagRule>().unwrap().to_string(), line);
+        }
+
+        let rule = "pale cyan bags contain 12 bright red bags, 1 dull tan bag.\r"
+            .parse::<BagRule>()
+            .unwrap();
+        assert_eq!(
+            rule.contents,
+            vec![(12, "bright red".to_string()), (1, "dull tan".to_string())]
+        );
+
+        let sloppy = "pale cyan bags contain 2 dull tan bag, 1 bright red bags.";
+        assert_eq!(
+            sloppy.parse::<BagRule>().unwrap().to_string(),
+            "pale cyan bags contain 2 dull tan bags, 1 bright red bag."
+        );
+    }
+
+    #[test]
+    fn malformed_rules() {
+        let error = |line: &str| {
+            let e = line.parse::<BagRule>().unwrap_err();
+            (e.column, e.kind, e.found)
+        };
+        assert_eq!(
+            error("pale cyan bags contain 0 dull tan bags."),
+            (24, ParseRuleErrorKind::ExpectedQuantity, "0".to_string())
+        );
+        assert_eq!(
+            error("pale cyan bags contain dull tan bags."),
+            (24, ParseRuleErrorKind::ExpectedQuantity, "dull".to_string())
+        );
+        assert_eq!(
+            error("bags contain no other bags."),
+            (1, ParseRuleErrorKind::ExpectedColor, "bags".to_string())
+        );
+        assert_eq!(
+            error("pale cyan bags hold no other bags."),
+            (
+                11,
+                ParseRuleErrorKind::Expected("bags contain "),
+                "bags".to_string()
+            )
+        );
+        assert_eq!(
+            error("pale cyan bags contain 1 dull tan bag"),
+            (38, ParseRuleErrorKind::Expected("."), "".to_string())
+        );
+        assert_eq!(
+            error("pale cyan bags contain 1 dull tan bag. extra, words."),
+            (
+                40,
+                ParseRuleErrorKind::TrailingText,
+                "extra, words.".to_string()
+            )
+        );
+        assert_eq!(
+            error("pale Cyan bags contain no other bags."),
+            (
+                6,
+                ParseRuleErrorKind::Expected("bags contain "),
+                "Cyan".to_string()
+            )
+        );
+
+        let err = get_values(
+            "faded blue bags contain no other bags.\ndotted black bags contain 1 faded blue.",
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "line 2, column 39: expected \" bags\", found \"\""
+        );
+    }
+
+    #[test]
+    fn dot_export() {
+        let graph = get_values(EXAMPLE).unwrap();
+        let shiny_gold = graph.id("shiny gold").unwrap();
+
+        assert_eq!(
+            graph.to_dot(DotScope::AncestorsOf(shiny_gold)),
+            r#"digraph bags {
+    "light red";
+    "bright white";
+    "muted yellow";
+    "dark orange";
+    "shiny gold";
+    "light red" -> "bright white" [label="1"];
+    "light red" -> "muted yellow" [label="2"];
+    "bright white" -> "shiny gold" [label="1"];
+    "muted yellow" -> "shiny gold" [label="2"];
+    "dark orange" -> "bright white" [label="3"];
+    "dark orange" -> "muted yellow" [label="4"];
+}
+"#
+        );
+
+        let descendants = graph.to_dot(DotScope::DescendantsOf(shiny_gold));
+        assert!(descendants.contains("\"vibrant plum\" -> \"dotted black\" [label=\"6\"];"));
+        assert!(!descendants.contains("muted yellow"));
+
+        let all = graph.to_dot(DotScope::All);
+        assert_eq!(all.lines().count(), 2 + 9 + 13);
+        assert_eq!(dot_id(r#"odd "quoted\ red"#), r#""odd \"quoted\\ red""#);
+    }
 }
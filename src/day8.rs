@@ -1,115 +1,256 @@
-use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fmt;
 
 use aoc_runner_derive::{aoc, aoc_generator};
-use regex::Regex;
 
-#[derive(Clone, Copy)]
-enum OpCode {
-    Nop,
-    Acc,
-    Jmp,
+use crate::assembler::{assemble, AssembleError};
+use crate::console::{Instruction, OpCode, State, Termination, Vm};
+
+#[aoc_generator(day8)]
+pub fn get_values(input: &str) -> Result<Vec<Instruction>, AssembleError> {
+    assemble(input)
 }
 
-impl OpCode {
-    fn from_str(value: &str) -> OpCode {
-        match value {
-            "nop" => OpCode::Nop,
-            "acc" => OpCode::Acc,
-            "jmp" => OpCode::Jmp,
-            _ => panic!("Invalid opcode"),
-        }
+/// The accumulator just before the program first repeats an instruction.
+#[aoc(day8, part1)]
+pub fn part1(program: &[Instruction]) -> Option<i64> {
+    let mut vm = Vm::new(program.to_vec());
+    match vm.run() {
+        Termination::InfiniteLoop { .. } => Some(vm.state().accumulator()),
+        _ => None,
     }
 }
 
-#[derive(Clone, Copy)]
-pub struct Instruction {
-    op_code: OpCode,
-    number: i32,
+/// Changing the instruction at `pc` to `replacement` makes the program halt, with `accumulator`
+/// as the final accumulator.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Repair {
+    pub pc: usize,
+    pub replacement: Instruction,
+    pub accumulator: i64,
 }
 
-enum ProgramOutput {
-    Error(i32),
-    Ok(i32),
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RepairError {
+    /// The program halts without changing anything.
+    AlreadyHalts,
+    /// No single `jmp`/`nop` swap makes the program halt.
+    Unrepairable,
+    /// More than one swap makes the program halt, at each of these addresses.
+    Ambiguous(Vec<usize>),
+    /// The program has a conditional jump at `pc`, so where each instruction leads isn't fixed.
+    Conditional { pc: usize },
+    /// The unchanged program stopped for a reason other than a loop or a jump out of bounds, so
+    /// the instructions it ran don't show which swaps would help.
+    Stopped(Termination),
 }
 
-#[aoc_generator(day8)]
-pub fn get_values(input: &str) -> HashMap<usize, Instruction> {
-    lazy_static! {
-        static ref PARSE_EXPR: Regex =
-            Regex::new(r"^(?P<op_code>nop|acc|jmp) (?P<number>[-+]\d+)$").unwrap();
+impl fmt::Display for RepairError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RepairError::AlreadyHalts => write!(f, "the program already halts"),
+            RepairError::Unrepairable => {
+                write!(f, "no single jmp/nop swap makes the program halt")
+            }
+            RepairError::Ambiguous(pcs) => write!(
+                f,
+                "swapping any of the instructions at {:?} makes the program halt",
+                pcs
+            ),
+            RepairError::Conditional { pc } => write!(
+                f,
+                "can't repair a program with a conditional jump, found one at pc {}",
+                pc
+            ),
+            RepairError::Stopped(termination) => {
+                write!(
+                    f,
+                    "can't repair a program that stopped with {}",
+                    termination
+                )
+            }
+        }
     }
+}
 
-    input
-        .lines()
-        .enumerate()
-        .fold(HashMap::new(), |mut acc, (idx, l)| {
-            let captures = PARSE_EXPR.captures(l).unwrap();
-            acc.insert(
-                idx + 1,
-                Instruction {
-                    op_code: OpCode::from_str(&captures["op_code"]),
-                    number: captures["number"].parse::<i32>().unwrap(),
-                },
-            );
-            acc
-        })
+impl Error for RepairError {}
+
+fn swapped(instruction: Instruction) -> Option<Instruction> {
+    let op_code = match instruction.op_code {
+        OpCode::Jmp => OpCode::Nop,
+        OpCode::Nop => OpCode::Jmp,
+        _ => return None,
+    };
+    Some(Instruction {
+        op_code,
+        ..instruction
+    })
 }
 
-fn evaluate_program(program: &HashMap<usize, Instruction>) -> ProgramOutput {
-    let mut current_line_number = 1;
-    let mut accumulator = 0;
-    let mut executed_lines: HashSet<usize> = HashSet::new();
-    loop {
-        if current_line_number > program.len() {
-            return ProgramOutput::Ok(accumulator);
+/// Finds every single `jmp`/`nop` swap that makes the program halt, in time linear in the length
+/// of the program.
+///
+/// Following each instruction to the one after it forms a forest rooted at the address just
+/// past the end and any `hlt` instructions, so walking that backwards finds every address that
+/// leads to a halt, and how much the accumulator changes on the way. A swap only matters if the
+/// unchanged program runs it, and it works if the swapped instruction leads to one of those
+/// addresses.
+pub fn find_repairs(program: &[Instruction]) -> Result<Vec<Repair>, RepairError> {
+    if let Some(pc) = program.iter().position(|i| i.op_code.is_conditional()) {
+        return Err(RepairError::Conditional { pc });
+    }
+    // without conditional jumps the registers don't affect where an instruction leads
+    let at = |pc| State {
+        pc,
+        ..State::default()
+    };
+
+    let end = program.len();
+    let mut previous = vec![vec![]; end + 1];
+    // the accumulator change from each address to the end, for addresses that halt
+    let mut to_end: Vec<Option<i64>> = vec![None; end + 1];
+    to_end[end] = Some(0);
+    let mut unexplored = vec![end];
+    for (pc, instruction) in program.iter().enumerate() {
+        match instruction.next_pc(&at(pc), end) {
+            Ok(next_pc) => previous[next_pc].push(pc),
+            Err(Termination::Halted) => {
+                to_end[pc] = Some(instruction.accumulator_change());
+                unexplored.push(pc);
+            }
+            Err(_) => {}
         }
-        if executed_lines.get(&current_line_number).is_some() {
-            return ProgramOutput::Error(accumulator);
+    }
+
+    while let Some(pc) = unexplored.pop() {
+        for prev in &previous[pc] {
+            to_end[*prev] = Some(program[*prev].accumulator_change() + to_end[pc].unwrap());
+            unexplored.push(*prev);
         }
-        executed_lines.insert(current_line_number);
+    }
 
-        let instruction = program.get(&current_line_number).unwrap();
-        match instruction.op_code {
-            OpCode::Acc => {
-                accumulator += instruction.number;
-                current_line_number += 1;
+    let mut repairs = vec![];
+    let mut vm = Vm::new(program.to_vec());
+    while let Some(instruction) = vm.next_instruction() {
+        let state = vm.state();
+        if let Some(replacement) = swapped(*instruction) {
+            let halts_after = replacement
+                .next_pc(&at(state.pc), end)
+                .ok()
+                .and_then(|next_pc| to_end[next_pc]);
+            if let Some(change) = halts_after {
+                repairs.push(Repair {
+                    pc: state.pc,
+                    replacement,
+                    accumulator: state.accumulator() + change,
+                });
             }
-            OpCode::Jmp => current_line_number += instruction.number as usize,
-            OpCode::Nop => {
-                current_line_number += 1;
+        }
+        vm.step();
+    }
+
+    // the loop above only ends once the machine has stopped
+    match vm.termination().unwrap() {
+        Termination::Halted => Err(RepairError::AlreadyHalts),
+        Termination::InfiniteLoop { .. }
+        | Termination::JumpedBeforeStart { .. }
+        | Termination::JumpedPastEnd { .. } => {
+            if repairs.is_empty() {
+                Err(RepairError::Unrepairable)
+            } else {
+                Ok(repairs)
             }
         }
+        termination => Err(RepairError::Stopped(termination)),
     }
 }
 
-#[aoc(day8, part1)]
-pub fn part1(program: &HashMap<usize, Instruction>) -> i32 {
-    match evaluate_program(&program) {
-        ProgramOutput::Error(v) => v,
-        ProgramOutput::Ok(_) => panic!("shouldn't have gotten here"),
+#[aoc(day8, part2)]
+pub fn part2(program: &[Instruction]) -> Result<i64, RepairError> {
+    match find_repairs(program)?[..] {
+        [repair] => Ok(repair.accumulator),
+        ref repairs => Err(RepairError::Ambiguous(
+            repairs.iter().map(|r| r.pc).collect(),
+        )),
     }
 }
 
-#[aoc(day8, part2)]
-pub fn part2(program_original: &HashMap<usize, Instruction>) -> i32 {
-    let mut line_number_to_change = 1;
-
-    loop {
-        let mut program = program_original.clone();
-        let current_line = program.entry(line_number_to_change);
-        current_line.and_modify(|mut instruction| {
-            match instruction.op_code {
-                OpCode::Jmp => instruction.op_code = OpCode::Nop,
-                OpCode::Nop => instruction.op_code = OpCode::Jmp,
-                OpCode::Acc => {}
-            };
-        });
-
-        match evaluate_program(&program) {
-            ProgramOutput::Ok(v) => return v,
-            ProgramOutput::Error(_) => {}
-        }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::console::DEFAULT_STEP_LIMIT;
+
+    const EXAMPLE: &str = "nop +0
+acc +1
+jmp +4
+acc +3
+jmp -3
+acc -99
+acc +1
+jmp -4
+acc +6";
+
+    #[test]
+    fn example() {
+        let program = get_values(EXAMPLE).unwrap();
+        assert_eq!(program.len(), 9);
+        assert_eq!(part1(&program), Some(5));
+        assert_eq!(part2(&program), Ok(8));
+        assert_eq!(
+            find_repairs(&program),
+            Ok(vec![Repair {
+                pc: 7,
+                replacement: Instruction::new(OpCode::Nop, -4),
+                accumulator: 8
+            }])
+        );
+    }
+
+    #[test]
+    fn no_repair() {
+        let program = get_values("acc +1\njmp +0\njmp -1").unwrap();
+        assert_eq!(part1(&program), Some(1));
+        assert_eq!(part2(&program), Err(RepairError::Unrepairable));
+
+        let program = get_values("nop +0\nacc +1").unwrap();
+        assert_eq!(part2(&program), Err(RepairError::AlreadyHalts));
+    }
+
+    #[test]
+    fn several_repairs() {
+        // the loop can be skipped from either end
+        let program = get_values("nop +3\nacc +1\njmp -2\nacc +10").unwrap();
+        let repairs = find_repairs(&program).unwrap();
+        assert_eq!(
+            repairs
+                .iter()
+                .map(|r| (r.pc, r.accumulator))
+                .collect::<Vec<_>>(),
+            vec![(0, 10), (2, 11)]
+        );
+        assert_eq!(part2(&program), Err(RepairError::Ambiguous(vec![0, 2])));
+    }
+
+    #[test]
+    fn extended_instructions() {
+        let program = get_values("acc +1\njmp +0\nhlt\njmp -1").unwrap();
+        assert_eq!(
+            find_repairs(&program).unwrap()[0],
+            Repair {
+                pc: 1,
+                replacement: Instruction::new(OpCode::Nop, 0),
+                accumulator: 1
+            }
+        );
+
+        let program = get_values("jz r1 +2\njmp +0\nhlt").unwrap();
+        assert_eq!(part2(&program), Err(RepairError::Conditional { pc: 0 }));
+    }
 
-        line_number_to_change += 1;
+    #[test]
+    fn long_programs() {
+        let program = vec![Instruction::new(OpCode::Nop, 0); DEFAULT_STEP_LIMIT + 1];
+        assert_eq!(part1(&program), None);
+        assert_eq!(find_repairs(&program), Err(RepairError::AlreadyHalts));
     }
 }
@@ -12,7 +12,7 @@ pub fn part1(inputs: &[usize]) -> usize {
     let mut window = inputs.iter().take(25).collect::<VecDeque<_>>();
 
     for number in inputs.iter().skip(25) {
-        if !is_valid(*number, &inputs) {
+        if !is_valid(*number, inputs) {
             return *number;
         }
         window.pop_front();
@@ -53,5 +53,5 @@ fn is_valid(number: usize, values: &[usize]) -> bool {
         }
     }
 
-    return false;
+    false
 }
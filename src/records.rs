@@ -0,0 +1,99 @@
+use std::ops::RangeInclusive;
+
+/// A run of consecutive non-blank lines from a batch file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Record<'a> {
+    /// The 1-based line number of the first line.
+    pub first_line: usize,
+    /// The lines of the record, without line endings or trailing whitespace.
+    pub lines: Vec<&'a str>,
+}
+
+impl<'a> Record<'a> {
+    /// The 1-based line numbers the record covers.
+    pub fn line_range(&self) -> RangeInclusive<usize> {
+        self.first_line..=self.first_line + self.lines.len() - 1
+    }
+
+    /// Each line paired with its 1-based line number.
+    pub fn numbered_lines(&self) -> impl Iterator<Item = (usize, &'a str)> + '_ {
+        self.lines
+            .iter()
+            .enumerate()
+            .map(move |(idx, line)| (self.first_line + idx, *line))
+    }
+}
+
+/// Splits a batch into records separated by blank lines. Lines holding only whitespace count as
+/// blank, both `\n` and `\r\n` line endings are accepted, and any number of blank lines may
+/// appear between, before or after the records.
+pub fn split_records(input: &str) -> Vec<Record<'_>> {
+    let mut records = vec![];
+    let mut current: Option<Record> = None;
+    for (idx, line) in input.lines().enumerate() {
+        let line = line.trim_end();
+        match (line.is_empty(), current.as_mut()) {
+            (true, _) => records.extend(current.take()),
+            (false, Some(record)) => record.lines.push(line),
+            (false, None) => {
+                current = Some(Record {
+                    first_line: idx + 1,
+                    lines: vec![line],
+                })
+            }
+        }
+    }
+    records.extend(current);
+    records
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(input: &str) -> Vec<Vec<&str>> {
+        split_records(input).into_iter().map(|r| r.lines).collect()
+    }
+
+    #[test]
+    fn unix_line_endings() {
+        assert_eq!(
+            lines("a\nb\n\nc\n\nd\ne\n"),
+            vec![vec!["a", "b"], vec!["c"], vec!["d", "e"]]
+        );
+    }
+
+    #[test]
+    fn windows_line_endings() {
+        assert_eq!(
+            lines("a\r\nb\r\n\r\nc\r\n"),
+            vec![vec!["a", "b"], vec!["c"]]
+        );
+        assert_eq!(lines("a\r\n\r\nb\r"), vec![vec!["a"], vec!["b"]]);
+    }
+
+    #[test]
+    fn whitespace_only_and_repeated_separators() {
+        assert_eq!(
+            lines("\n\n  \na b  \n \t \n\n\nc\n   \n"),
+            vec![vec!["a b"], vec!["c"]]
+        );
+    }
+
+    #[test]
+    fn empty_input() {
+        assert!(split_records("").is_empty());
+        assert!(split_records("\n \r\n\t\n").is_empty());
+    }
+
+    #[test]
+    fn line_numbers() {
+        let records = split_records("\na\nb\n\n\nc\n");
+        assert_eq!(records[0].line_range(), 2..=3);
+        assert_eq!(records[1].line_range(), 6..=6);
+        assert_eq!(
+            records[0].numbered_lines().collect::<Vec<(usize, &str)>>(),
+            vec![(2, "a"), (3, "b")]
+        );
+    }
+}